@@ -0,0 +1,54 @@
+use std::error::Error;
+use std::fmt::{self, Formatter};
+use tonic::Status;
+
+pub type Result<T> = std::result::Result<T, CrobatError>;
+
+#[derive(Debug)]
+pub enum CrobatError {
+    /// The endpoint isn't a valid uri
+    InvalidUri(String),
+    /// Couldn't connect to the endpoint
+    ConnectError(tonic::transport::Error),
+    /// Couldn't set up TLS for the endpoint
+    TlsError(tonic::transport::Error),
+    /// The server responded to the request with a non-ok status
+    StatusError(Box<Status>),
+    /// The response stream failed part of the way through
+    StreamError(Box<Status>),
+}
+
+impl fmt::Display for CrobatError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CrobatError::InvalidUri(s) => write!(f, "invalid crobat endpoint {}", s),
+            CrobatError::ConnectError(ref err) => write!(f, "couldn't connect to crobat: {}", err),
+            CrobatError::TlsError(ref err) => write!(f, "couldn't set up tls for crobat: {}", err),
+            CrobatError::StatusError(ref s) => {
+                write!(f, "crobat returned status {:?}: {}", s.code(), s.message())
+            }
+            CrobatError::StreamError(ref s) => write!(
+                f,
+                "crobat stream failed with status {:?}: {}",
+                s.code(),
+                s.message()
+            ),
+        }
+    }
+}
+
+impl Error for CrobatError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CrobatError::ConnectError(ref err) | CrobatError::TlsError(ref err) => Some(err),
+            CrobatError::StatusError(ref s) | CrobatError::StreamError(ref s) => Some(s.as_ref()),
+            CrobatError::InvalidUri(_) => None,
+        }
+    }
+}
+
+impl From<Status> for CrobatError {
+    fn from(status: Status) -> Self {
+        CrobatError::StatusError(Box::new(status))
+    }
+}
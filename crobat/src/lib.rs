@@ -10,40 +10,71 @@ extern crate log;
 use crobat::crobat_client::CrobatClient;
 use crobat::Domain;
 use crobat::QueryRequest;
+use crobat::ReverseResult;
+pub use error::{CrobatError, Result};
 use futures_core::stream::Stream;
+use futures_util::StreamExt;
 use std::sync::Arc;
 use tonic::transport::{Channel, ClientTlsConfig};
-use tonic::Status;
 
-pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+pub mod error;
 
+/// The public SonarSearch instance
+pub const DEFAULT_ENDPOINT: &str = "https://crobat-rpc.omnisint.io";
+
+/// A client for a SonarSearch instance.
+///
+/// Cloning is cheap and clones share the same underlying connection.
+#[derive(Clone)]
 pub struct Crobat {
     client: CrobatClient<Channel>,
 }
 
 impl Crobat {
+    /// Connects to the public SonarSearch instance
     pub async fn connect() -> Result<Self> {
-        trace!("building crobat client");
-        let addr = "https://crobat-rpc.omnisint.io";
-        match Crobat::build_tls_client(addr).await {
-            Ok(conn) => Ok(Self {
-                client: CrobatClient::new(conn),
-            }),
-            Err(e) => Err(e),
-        }
+        Crobat::connect_to(DEFAULT_ENDPOINT).await
+    }
+
+    /// Connects to the SonarSearch instance at `url`. `https` urls are connected to over TLS
+    /// and `http` urls in plaintext, which is handy for a local instance.
+    pub async fn connect_to(url: &str) -> Result<Self> {
+        trace!("building crobat client for {}", url);
+        let conn = if url.starts_with("http://") {
+            Crobat::build_plaintext_client(url).await?
+        } else {
+            Crobat::build_tls_client(url).await?
+        };
+
+        Ok(Self {
+            client: CrobatClient::new(conn),
+        })
     }
 
-    async fn build_tls_client(url: &'static str) -> Result<Channel> {
+    async fn build_tls_client(url: &str) -> Result<Channel> {
         let mut config = rustls::ClientConfig::new();
         config
             .root_store
             .add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS);
         config.alpn_protocols = vec![b"h2".to_vec()];
 
-        let conn = Channel::from_static(url)
-            .tls_config(ClientTlsConfig::new().rustls_client_config(config))?
+        let conn = Channel::from_shared(url.to_string())
+            .map_err(|e| CrobatError::InvalidUri(format!("{}: {}", url, e)))?
+            .tls_config(ClientTlsConfig::new().rustls_client_config(config))
+            .map_err(CrobatError::TlsError)?
+            .connect()
+            .await
+            .map_err(CrobatError::ConnectError)?;
+
+        Ok(conn)
+    }
+
+    async fn build_plaintext_client(url: &str) -> Result<Channel> {
+        let conn = Channel::from_shared(url.to_string())
+            .map_err(|e| CrobatError::InvalidUri(format!("{}: {}", url, e)))?
             .connect()
-            .await?;
+            .await
+            .map_err(CrobatError::ConnectError)?;
 
         Ok(conn)
     }
@@ -51,13 +82,56 @@ impl Crobat {
     pub async fn get_subs(
         &mut self,
         host: Arc<String>,
-    ) -> Result<impl Stream<Item = std::result::Result<Domain, Status>>> {
+    ) -> Result<impl Stream<Item = Result<Domain>>> {
         trace!("querying crobat client for subdomains");
         let request = tonic::Request::new(QueryRequest {
             query: host.to_string(),
         });
         let stream = self.client.get_subdomains(request).await?.into_inner();
 
-        Ok(stream)
+        Ok(stream.map(|r| r.map_err(|s| CrobatError::StreamError(Box::new(s)))))
+    }
+
+    /// Finds the same name under other top level domains, for example `hackerone` would
+    /// return `hackerone.com`, `hackerone.net` and so on.
+    pub async fn get_tlds(
+        &mut self,
+        name: Arc<String>,
+    ) -> Result<impl Stream<Item = Result<Domain>>> {
+        trace!("querying crobat client for tlds");
+        let request = tonic::Request::new(QueryRequest {
+            query: name.to_string(),
+        });
+        let stream = self.client.get_tl_ds(request).await?.into_inner();
+
+        Ok(stream.map(|r| r.map_err(|s| CrobatError::StreamError(Box::new(s)))))
+    }
+
+    /// Finds the hostnames which resolve to an ip address.
+    pub async fn reverse_dns(
+        &mut self,
+        ip: Arc<String>,
+    ) -> Result<impl Stream<Item = Result<Domain>>> {
+        trace!("querying crobat client for reverse dns");
+        let request = tonic::Request::new(QueryRequest {
+            query: ip.to_string(),
+        });
+        let stream = self.client.reverse_dns(request).await?.into_inner();
+
+        Ok(stream.map(|r| r.map_err(|s| CrobatError::StreamError(Box::new(s)))))
+    }
+
+    /// Finds the hostnames for every ip address in a cidr range, such as `104.16.99.0/24`.
+    pub async fn reverse_dns_range(
+        &mut self,
+        cidr: Arc<String>,
+    ) -> Result<impl Stream<Item = Result<ReverseResult>>> {
+        trace!("querying crobat client for reverse dns range");
+        let request = tonic::Request::new(QueryRequest {
+            query: cidr.to_string(),
+        });
+        let stream = self.client.reverse_dns_range(request).await?.into_inner();
+
+        Ok(stream.map(|r| r.map_err(|s| CrobatError::StreamError(Box::new(s)))))
     }
 }
@@ -1,26 +1,35 @@
 extern crate vita;
-use clap::{App, Arg};
+use clap::{App, Arg, ArgMatches, SubCommand};
 use futures::stream::StreamExt;
 use std::collections::HashSet;
 use std::env;
 use std::fs::File;
 use std::io::{self, BufRead, BufReader};
-use vita::error::Result;
-use vita::{CleanExt, PostProcessor, Runner};
+use vita::error::{Result, VitaError};
+use vita::{CleanExt, PostProcessor, Runner, Watcher};
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let matches = create_clap_app().get_matches();
+    if let Some(matches) = matches.subcommand_matches("watch") {
+        return watch(matches).await;
+    }
+
     let ParsedArgs {
         runner,
         cleaner,
         flush,
+        ips,
+        report,
         hosts,
-    } = ParsedArgs::new(create_clap_app())?;
+    } = ParsedArgs::new(&matches)?;
     let mut results: HashSet<String> = HashSet::new();
 
+    let run_report = runner.report();
     let mut stream = runner.run(hosts).await?;
     while let Some(v) = stream.next().await {
-        v.iter().clean(&cleaner).for_each(|r| {
+        v.into_iter().clean(&cleaner).for_each(|s| {
+            let r = if ips { s.to_string() } else { s.host };
             if flush {
                 println!("{}", r);
             } else {
@@ -33,6 +42,10 @@ async fn main() -> Result<()> {
         results.iter().for_each(|r| println!("{}", r));
     }
 
+    if report {
+        eprint!("{}", run_report);
+    }
+
     Ok(())
 }
 
@@ -40,63 +53,123 @@ struct ParsedArgs {
     runner: Runner,
     cleaner: PostProcessor,
     flush: bool,
+    ips: bool,
+    report: bool,
     hosts: HashSet<String>,
 }
 
 impl ParsedArgs {
-    fn new(app: clap::App<'static, 'static>) -> Result<Self> {
-        let matches = app.get_matches();
-        // make it a hashset incase user provided duplicate domains
-        let mut hosts: HashSet<String> = HashSet::new();
+    fn new(matches: &ArgMatches) -> Result<Self> {
         let mut excluded: Vec<&str> = Vec::new();
         let max_concurrent: usize = matches.value_of("concurrency").unwrap().parse()?;
         let timeout: u64 = matches.value_of("timeout").unwrap().parse()?;
 
-        if matches.is_present("verbosity") {
-            let builder = tracing_subscriber::fmt()
-                .with_env_filter(matches.value_of("verbosity").unwrap())
-                .with_filter_reloading();
-            let _handle = builder.reload_handle();
-            builder.try_init()?;
-        }
-
-        if matches.is_present("file") {
-            let input = matches.value_of("input").unwrap();
-            hosts = read_input(Some(input))?;
-        } else if matches.is_present("domain") {
-            hosts.insert(matches.value_of("input").unwrap().to_string());
-        } else {
-            hosts = read_input(None)?;
-        }
+        init_tracing(matches)?;
+        let hosts = read_hosts(matches)?;
 
         if matches.is_present("exclude") {
             excluded = matches.values_of("exclude").unwrap().collect();
         }
 
         let mut cleaner = PostProcessor::default();
-        if matches.is_present("subs-only") {
+        if matches.is_present("tlds") || matches.is_present("reverse") {
+            cleaner.any();
+        } else if matches.is_present("subs-only") {
             cleaner.any_subdomain(hosts.clone());
         } else {
             cleaner.any_root(hosts.clone());
         }
 
+        if matches.is_present("related") {
+            cleaner.keep_related(true);
+            // Read by the Security Trails source when it's created
+            env::set_var("SECURITY_TRAILS_ASSOCIATED", "true");
+        }
+
         let mut runner = Runner::default()
             .concurrency(max_concurrent)
-            .timeout(timeout)
-            .free_sources()
-            .exclude(&excluded);
-        if matches.is_present("all_sources") {
-            runner = runner.all_sources().exclude(&excluded);
+            .timeout(timeout);
+        if matches.is_present("tlds") {
+            runner = runner.tlds();
+        } else if matches.is_present("reverse") {
+            runner = runner.reverse_dns();
+        } else {
+            runner = if matches.is_present("all_sources") {
+                runner.all_sources()
+            } else {
+                runner.free_sources()
+            };
+
+            if let Some(path) = matches.value_of("config") {
+                runner = runner.json_sources(vita::sources::custom::load(path)?);
+            }
+            runner = runner.exclude(&excluded);
         }
 
         Ok(Self {
             runner,
             cleaner,
             flush: matches.is_present("flush"),
+            ips: matches.is_present("ips"),
+            report: matches.is_present("report"),
             hosts,
         })
     }
 }
+/// Watches certstream for new subdomains of the input domains, printing each one the first time
+/// it's seen.
+async fn watch(matches: &ArgMatches<'_>) -> Result<()> {
+    init_tracing(matches)?;
+    let hosts = read_hosts(matches)?;
+
+    let mut cleaner = PostProcessor::default();
+    if matches.is_present("subs-only") {
+        cleaner.any_subdomain(hosts);
+    } else {
+        cleaner.any_root(hosts);
+    }
+
+    let mut stream = Watcher::new(cleaner)
+        .url(matches.value_of("url").unwrap())
+        .run();
+    while let Some(s) = stream.next().await {
+        println!("{}", s.host);
+    }
+
+    Ok(())
+}
+
+/// Sets up logging to stderr if a verbosity was given
+fn init_tracing(matches: &ArgMatches) -> Result<()> {
+    if matches.is_present("verbosity") {
+        let builder = tracing_subscriber::fmt()
+            .with_env_filter(matches.value_of("verbosity").unwrap())
+            .with_filter_reloading();
+        let _handle = builder.reload_handle();
+        builder
+            .try_init()
+            .map_err(|e| VitaError::Msg(e.to_string()))?;
+    }
+
+    Ok(())
+}
+
+/// Reads the input domains from the arguments, a file or stdin
+fn read_hosts(matches: &ArgMatches) -> Result<HashSet<String>> {
+    // make it a hashset incase user provided duplicate domains
+    let mut hosts: HashSet<String> = HashSet::new();
+    if matches.is_present("file") {
+        let input = matches.value_of("input").unwrap();
+        hosts = read_input(Some(input))?;
+    } else if matches.is_present("domain") {
+        hosts.insert(matches.value_of("input").unwrap().to_string());
+    } else {
+        hosts = read_input(None)?;
+    }
+
+    Ok(hosts)
+}
+
 /// Reads input from stdin or a file
 fn read_input(path: Option<&str>) -> Result<HashSet<String>> {
     let mut contents = HashSet::new();
@@ -149,11 +222,36 @@ fn create_clap_app() -> clap::App<'static, 'static> {
                 .multiple(true)
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("config")
+                .help("A toml file declaring extra json sources to use")
+                .long("config")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("subs-only")
                 .help("filter the results to only those which have the same subdomain")
                 .long("subs-only"),
         )
+        .arg(
+            Arg::with_name("tlds")
+                .help("find the same name as the input domains under other top level domains")
+                .long("tlds")
+                .conflicts_with("reverse"),
+        )
+        .arg(
+            Arg::with_name("reverse")
+                .help("treat the input as ip addresses or cidr ranges and find their hostnames")
+                .long("reverse"),
+        )
+        .arg(
+            Arg::with_name("related")
+                .help(
+                    "Keeps results related to the input domains, like Security Trails associated
+                    domains and CNAME targets in other zones, instead of filtering them out",
+                )
+                .long("related"),
+        )
         .arg(
             Arg::with_name("flush")
                 .help(
@@ -162,6 +260,16 @@ fn create_clap_app() -> clap::App<'static, 'static> {
                 )
                 .long("flush"),
         )
+        .arg(
+            Arg::with_name("ips")
+                .help("Prints the ip address next to each result when the source provides one")
+                .long("ips"),
+        )
+        .arg(
+            Arg::with_name("report")
+                .help("Prints a summary of how each source did to stderr once the run is finished")
+                .long("report"),
+        )
         .arg(
             Arg::with_name("concurrency")
                 .help("The number of domains to fetch data for concurrently")
@@ -191,4 +299,44 @@ fn create_clap_app() -> clap::App<'static, 'static> {
                 .default_value("15")
                 .takes_value(true),
         )
+        .subcommand(
+            SubCommand::with_name("watch")
+                .about("Watches certstream for newly issued certificates for the input domains")
+                .usage("vita watch -d <domain.com>")
+                .arg(Arg::with_name("input").index(1).required(false))
+                .arg(
+                    Arg::with_name("file")
+                        .help("vita watch -f <roots.txt>")
+                        .short("f")
+                        .long("file"),
+                )
+                .arg(
+                    Arg::with_name("domain")
+                        .help("vita watch -d domain.com")
+                        .short("d")
+                        .long("domain"),
+                )
+                .arg(
+                    Arg::with_name("subs-only")
+                        .help("filter the results to only those which have the same subdomain")
+                        .long("subs-only"),
+                )
+                .arg(
+                    Arg::with_name("url")
+                        .help("The certstream compatible websocket feed to watch")
+                        .long("url")
+                        .default_value(vita::watch::DEFAULT_CERTSTREAM_URL)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("verbosity")
+                        .help(
+                            "different levels of verbosity you can set for debugging, 
+                            values include: debug,info and warn",
+                        )
+                        .short("v")
+                        .long("verbosity")
+                        .takes_value(true),
+                ),
+        )
 }
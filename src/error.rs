@@ -7,13 +7,19 @@ pub type Result<T> = std::result::Result<T, VitaError>;
 pub enum VitaError {
     SourceError(String),
     AuthError(String),
+    QuotaExceeded(String),
     UnsetKeys(Vec<String>),
     ReqwestError(reqwest::Error),
+    PostgresError(tokio_postgres::Error),
     JoinError(tokio::task::JoinError),
     IoError(std::io::Error),
+    JsonError(serde_json::Error),
+    ZipError(zip::result::ZipError),
+    TomlError(toml::de::Error),
+    SqliteError(rusqlite::Error),
     Msg(String),
     ParseError,
-    CrobatError,
+    CrobatError(crobat::CrobatError),
     EmptyResults,
 }
 
@@ -28,16 +34,26 @@ impl fmt::Display for VitaError {
                     s
                 )
             }
+            VitaError::QuotaExceeded(s) => write!(f, "api quota for {} has been used up", s),
             VitaError::UnsetKeys(v) => write!(f, "error reading environment variables {:?}", v),
             VitaError::EmptyResults => write!(f, "returned no results"),
-            VitaError::CrobatError => {
-                write!(f, "got error when trying to pull results from crobat")
+            VitaError::CrobatError(ref err) => {
+                write!(
+                    f,
+                    "got error when trying to pull results from crobat: {}",
+                    err
+                )
             }
             VitaError::ParseError => write!(f, "got error trying to parse cli args"),
             VitaError::Msg(s) => write!(f, "got error {}", s),
             VitaError::ReqwestError(ref err) => err.fmt(f),
+            VitaError::PostgresError(ref err) => err.fmt(f),
             VitaError::JoinError(ref err) => err.fmt(f),
             VitaError::IoError(ref err) => err.fmt(f),
+            VitaError::JsonError(ref err) => err.fmt(f),
+            VitaError::ZipError(ref err) => err.fmt(f),
+            VitaError::TomlError(ref err) => err.fmt(f),
+            VitaError::SqliteError(ref err) => err.fmt(f),
         }
     }
 }
@@ -56,6 +72,12 @@ impl From<reqwest::Error> for VitaError {
     }
 }
 
+impl From<tokio_postgres::Error> for VitaError {
+    fn from(err: tokio_postgres::Error) -> Self {
+        VitaError::PostgresError(err)
+    }
+}
+
 impl From<tokio::task::JoinError> for VitaError {
     fn from(err: tokio::task::JoinError) -> Self {
         VitaError::JoinError(err)
@@ -68,15 +90,38 @@ impl From<std::io::Error> for VitaError {
     }
 }
 
+impl From<serde_json::Error> for VitaError {
+    fn from(err: serde_json::Error) -> Self {
+        VitaError::JsonError(err)
+    }
+}
+
+impl From<zip::result::ZipError> for VitaError {
+    fn from(err: zip::result::ZipError) -> Self {
+        VitaError::ZipError(err)
+    }
+}
+
+impl From<toml::de::Error> for VitaError {
+    fn from(err: toml::de::Error) -> Self {
+        VitaError::TomlError(err)
+    }
+}
+
+impl From<rusqlite::Error> for VitaError {
+    fn from(err: rusqlite::Error) -> Self {
+        VitaError::SqliteError(err)
+    }
+}
+
 impl From<std::num::ParseIntError> for VitaError {
     fn from(_: std::num::ParseIntError) -> Self {
         VitaError::ParseError
     }
 }
 
-// Monkey patch until I add custom error type to Crobat
-impl From<Box<dyn Error + Sync + std::marker::Send>> for VitaError {
-    fn from(_: Box<dyn Error + Sync + std::marker::Send>) -> Self {
-        VitaError::CrobatError
+impl From<crobat::CrobatError> for VitaError {
+    fn from(err: crobat::CrobatError) -> Self {
+        VitaError::CrobatError(err)
     }
 }
@@ -4,24 +4,141 @@ pub use crate::vita::Runner;
 use async_trait::async_trait;
 use error::Result;
 pub use postprocessor::{CleanExt, PostProcessor, PostProcessorIter};
+use regex::Regex;
+pub use report::Report;
+use std::collections::HashSet;
+use std::fmt;
+use std::net::IpAddr;
 use std::sync::Arc;
 use tokio::sync::mpsc;
+pub use watch::Watcher;
 
 pub mod error;
 pub mod postprocessor;
+pub mod report;
 pub mod sources;
 pub mod vita;
+pub mod watch;
 
 // Arbitrary number for the queue capacity
 pub(crate) const QUEUE_SIZE: usize = 1024;
 
+/// A dns record a source found a host in, like a CNAME and the name it points at.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Record {
+    pub record_type: String,
+    pub value: String,
+}
+
+/// A result from one of the sources, with the ip address the host resolved to if the source
+/// provided one.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Subdomain {
+    pub host: String,
+    pub ip: Option<IpAddr>,
+    /// The record the host was found in, when the source provided one.
+    pub record: Option<Record>,
+    /// Set for results which aren't under the input domain but are related to it, like a
+    /// domain owned by the same organisation or a CNAME target in another zone.
+    pub related: bool,
+}
+
+impl Subdomain {
+    /// Creates a `Subdomain` along with its ip, the ip is dropped if it can't be parsed.
+    pub fn with_ip<S: Into<String>>(host: S, ip: &str) -> Self {
+        Self {
+            ip: ip.trim().parse().ok(),
+            ..Self::from(host)
+        }
+    }
+
+    /// Creates a `Subdomain` along with the record it was found in.
+    pub fn with_record<S: Into<String>>(host: S, record_type: &str, value: &str) -> Self {
+        Self {
+            record: Some(Record {
+                record_type: record_type.to_string(),
+                value: value.to_string(),
+            }),
+            ..Self::from(host)
+        }
+    }
+
+    /// Creates a `Subdomain` which is related to the input domain rather than under it.
+    pub fn related<S: Into<String>>(host: S) -> Self {
+        Self {
+            related: true,
+            ..Self::from(host)
+        }
+    }
+}
+
+impl<S: Into<String>> From<S> for Subdomain {
+    fn from(host: S) -> Self {
+        Self {
+            host: host.into(),
+            ip: None,
+            record: None,
+            related: false,
+        }
+    }
+}
+
+impl fmt::Display for Subdomain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.ip {
+            Some(ip) => write!(f, "{},{}", self.host, ip),
+            None => write!(f, "{}", self.host),
+        }
+    }
+}
+
 trait IntoSubdomain {
-    fn subdomains(&self) -> Vec<String>;
+    fn subdomains(&self) -> Vec<Subdomain>;
+}
+
+/// Matches any hostname under `host` in free text, used with `find_hosts`. A percent encoded
+/// character in front of the name, like the `%2F` in an encoded url, is matched as well so it can
+/// be stripped off.
+pub(crate) fn host_regex(host: &str) -> Regex {
+    Regex::new(&format!(
+        r"(?i)(?:%[0-9a-f]{{2}})?(?:[a-z0-9](?:[a-z0-9-]{{0,61}}[a-z0-9])?\.)+{}",
+        regex::escape(host)
+    ))
+    .unwrap()
+}
+
+/// Returns the lowercased hostnames `re` finds in `text`. Matches which carry on into a longer
+/// name, like `hackerone.com.evil.io`, are dropped, though a trailing dot on its own is fine.
+pub(crate) fn find_hosts(re: &Regex, text: &str) -> Vec<String> {
+    let is_label = |c: char| c.is_ascii_alphanumeric() || c == '-';
+    re.find_iter(text)
+        .filter(|m| {
+            let rest = &text[m.end()..];
+            !rest.starts_with(is_label) && !rest.trim_start_matches('.').starts_with(is_label)
+        })
+        .map(|m| {
+            let host = m.as_str();
+            match host.strip_prefix('%') {
+                Some(rest) => rest.get(2..).unwrap_or(rest),
+                None => host,
+            }
+            .to_lowercase()
+        })
+        .collect()
 }
 
 #[async_trait]
 trait DataSource: Send + Sync {
-    async fn run(&self, host: Arc<String>, mut tx: mpsc::Sender<Vec<String>>) -> Result<()>;
+    async fn run(&self, host: Arc<String>, mut tx: mpsc::Sender<Vec<Subdomain>>) -> Result<()>;
+
+    /// Called with every host before any are searched, for sources which are better off looking
+    /// for all of them at once.
+    fn prepare(&self, _hosts: &HashSet<String>) {}
+
+    /// Extra information to include in the run report, like the remaining api quota.
+    fn notes(&self) -> Vec<String> {
+        Vec::new()
+    }
 }
 
 #[macro_export]
@@ -1,6 +1,6 @@
+use crate::Subdomain;
 use addr::DomainName;
 use std::collections::HashSet;
-use std::hash::Hash;
 
 /// Represents the filtering applied to the output
 enum Filter {
@@ -8,6 +8,8 @@ enum Filter {
     SubOnly,
     /// Return any result that has the same root domain
     RootOnly,
+    /// Return every result
+    Any,
 }
 
 impl Default for Filter {
@@ -22,6 +24,7 @@ impl Default for Filter {
 pub struct PostProcessor {
     roots: HashSet<String>,
     filter: Filter,
+    related: bool,
 }
 
 impl PostProcessor {
@@ -43,6 +46,23 @@ impl PostProcessor {
         self
     }
 
+    /// Sets the `PostProcessor` to return every result, only stripping invalid characters.
+    ///
+    /// Useful when the results aren't expected to share a root with the input, such as when
+    /// looking up other top level domains or doing reverse dns.
+    pub fn any(&mut self) -> &mut Self {
+        self.roots.clear();
+        self.filter = Filter::Any;
+        self
+    }
+
+    /// Keeps results the sources marked as related to the input, like associated domains, even
+    /// when they don't pass the filter.
+    pub fn keep_related(&mut self, keep: bool) -> &mut Self {
+        self.related = keep;
+        self
+    }
+
     /// Strips invalid characters from the domain
     ///
     /// Used before attempting to parse a domain into  a `add::DomainName`.
@@ -80,6 +100,7 @@ impl PostProcessor {
                 .roots
                 .iter()
                 .any(|root| result.as_ref().ends_with(root) && !result.as_ref().eq(root)),
+            Filter::Any => !result.as_ref().is_empty(),
         }
     }
 }
@@ -96,15 +117,18 @@ where
 impl<'a, I> Iterator for PostProcessorIter<'a, I>
 where
     I: Iterator,
-    I::Item: Hash + Eq + AsRef<str>,
+    I::Item: Into<Subdomain>,
 {
-    type Item = String;
+    type Item = Subdomain;
 
     fn next(&mut self) -> Option<Self::Item> {
         while let Some(d) = self.inner.next() {
-            let cleaned = PostProcessor::strip_invalid(d.as_ref());
-            if self.cleaner.is_relevant(&cleaned) {
-                return Some(cleaned);
+            let mut subdomain: Subdomain = d.into();
+            subdomain.host = PostProcessor::strip_invalid(&subdomain.host);
+            if self.cleaner.is_relevant(&subdomain.host)
+                || (subdomain.related && self.cleaner.related && !subdomain.host.is_empty())
+            {
+                return Some(subdomain);
             }
         }
         None
@@ -114,7 +138,7 @@ where
 pub trait CleanExt: Iterator {
     fn clean(self, postprocessor: &PostProcessor) -> PostProcessorIter<Self>
     where
-        Self::Item: Hash + Eq + AsRef<str>,
+        Self::Item: Into<Subdomain>,
         Self: Sized,
     {
         PostProcessorIter {
@@ -0,0 +1,96 @@
+use crate::error::Result;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// How a single source did across every host it was run on.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SourceReport {
+    /// The number of hosts the source returned results for
+    pub succeeded: usize,
+    /// The number of hosts the source failed or returned nothing for
+    pub failed: usize,
+    /// The last error the source returned
+    pub last_error: Option<String>,
+    /// Anything else the source wants to tell the user, like how much of their api quota is left
+    pub notes: Vec<String>,
+}
+
+/// A summary of how each source did during a run. It's a cheap handle to shared state, so it
+/// can be taken from the `Runner` before the run and read once the results have been consumed.
+#[derive(Debug, Default, Clone)]
+pub struct Report {
+    sources: Arc<Mutex<BTreeMap<String, SourceReport>>>,
+}
+
+impl Report {
+    pub(crate) fn record(&self, source: &str, result: &Result<()>, notes: Vec<String>) {
+        let mut sources = self.sources.lock().unwrap();
+        let report = sources.entry(source.to_string()).or_default();
+        match result {
+            Ok(_) => report.succeeded += 1,
+            Err(e) => {
+                report.failed += 1;
+                report.last_error = Some(e.to_string());
+            }
+        }
+
+        // Notes describe the current state of the source so only the latest ones are kept
+        if !notes.is_empty() {
+            report.notes = notes;
+        }
+    }
+
+    /// Returns a snapshot of the report for each source, ordered by name.
+    pub fn sources(&self) -> BTreeMap<String, SourceReport> {
+        self.sources.lock().unwrap().clone()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, report) in self.sources().iter() {
+            write!(
+                f,
+                "{}: {} succeeded, {} failed",
+                name, report.succeeded, report.failed
+            )?;
+            if let Some(e) = &report.last_error {
+                write!(f, " (last error: {})", e)?;
+            }
+            writeln!(f)?;
+
+            for note in report.notes.iter() {
+                writeln!(f, "  {}", note)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::VitaError;
+
+    #[test]
+    fn records_results() {
+        let report = Report::default();
+        report.record("Crtsh", &Ok(()), Vec::new());
+        report.record(
+            "Crtsh",
+            &Err(VitaError::SourceError("Crtsh".into())),
+            Vec::new(),
+        );
+        report.record("PassiveTotal", &Ok(()), vec!["quota: 1/15".into()]);
+        report.record("PassiveTotal", &Ok(()), vec!["quota: 2/15".into()]);
+
+        let sources = report.sources();
+        assert_eq!(sources["Crtsh"].succeeded, 1);
+        assert_eq!(sources["Crtsh"].failed, 1);
+        assert!(sources["Crtsh"].last_error.is_some());
+        assert_eq!(sources["PassiveTotal"].succeeded, 2);
+        assert_eq!(sources["PassiveTotal"].notes, vec!["quota: 2/15"]);
+    }
+}
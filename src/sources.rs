@@ -2,14 +2,23 @@ pub mod alienvault;
 pub mod anubisdb;
 pub mod binaryedge;
 pub mod c99;
+pub mod censys;
 pub mod certspotter;
 pub mod chaos;
+pub mod commoncrawl;
 pub mod crtsh;
+pub mod ctlogs;
+pub mod custom;
+pub mod dnsdumpster;
 pub mod facebook;
+pub mod github;
 pub mod hackertarget;
 pub mod intelx;
+pub mod local;
 pub mod passivetotal;
+pub mod scrape;
 pub mod securitytrails;
+pub mod shodan;
 pub mod sonarsearch;
 pub mod spyse;
 pub mod sublister;
@@ -1,5 +1,5 @@
 use crate::error::{Result, VitaError};
-use crate::{DataSource, IntoSubdomain};
+use crate::{DataSource, IntoSubdomain, Subdomain};
 use async_trait::async_trait;
 use reqwest::Client;
 use serde::Deserialize;
@@ -8,21 +8,21 @@ use tokio::sync::mpsc::Sender;
 use tracing::{info, trace, warn};
 
 #[derive(Deserialize, Debug)]
-struct Subdomain {
+struct AlienvaultRecord {
     hostname: String,
 }
 
 #[derive(Deserialize, Debug)]
 struct AlienvaultResult {
-    passive_dns: Vec<Subdomain>,
+    passive_dns: Vec<AlienvaultRecord>,
     count: i32,
 }
 
 impl IntoSubdomain for AlienvaultResult {
-    fn subdomains(&self) -> Vec<String> {
+    fn subdomains(&self) -> Vec<Subdomain> {
         self.passive_dns
             .iter()
-            .map(|s| s.hostname.to_owned())
+            .map(|s| Subdomain::from(&s.hostname))
             .collect()
     }
 }
@@ -47,7 +47,7 @@ impl AlienVault {
 
 #[async_trait]
 impl DataSource for AlienVault {
-    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<String>>) -> Result<()> {
+    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<Subdomain>>) -> Result<()> {
         trace!("fetching data from alienvault for: {}", &host);
         let uri = self.build_url(&host);
         let resp: AlienvaultResult = self.client.get(&uri).send().await?.json().await?;
@@ -1,5 +1,5 @@
 use crate::error::{Result, VitaError};
-use crate::{DataSource, IntoSubdomain};
+use crate::{DataSource, IntoSubdomain, Subdomain};
 use async_trait::async_trait;
 use reqwest::Client;
 use serde_json::value::Value;
@@ -18,9 +18,12 @@ impl AnubisResult {
 }
 
 impl IntoSubdomain for AnubisResult {
-    fn subdomains(&self) -> Vec<String> {
+    fn subdomains(&self) -> Vec<Subdomain> {
         match self.results.as_array() {
-            Some(array) => array.iter().map(|s| s.to_string()).collect(),
+            Some(array) => array
+                .iter()
+                .map(|s| Subdomain::from(s.to_string()))
+                .collect(),
             None => Vec::new(),
         }
     }
@@ -43,7 +46,7 @@ impl AnubisDB {
 
 #[async_trait]
 impl DataSource for AnubisDB {
-    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<String>>) -> Result<()> {
+    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<Subdomain>>) -> Result<()> {
         trace!("fetching data from anubisdb for: {}", &host);
         let uri = self.build_url(&host);
         let resp: Option<Value> = self.client.get(&uri).send().await?.json().await?;
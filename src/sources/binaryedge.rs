@@ -1,8 +1,9 @@
 use crate::error::Result;
 use crate::error::VitaError;
-use crate::{DataSource, IntoSubdomain};
+use crate::{DataSource, IntoSubdomain, Subdomain};
 use async_trait::async_trait;
 use dotenv::dotenv;
+use futures::stream::{self, StreamExt};
 use reqwest::Client;
 use serde::Deserialize;
 use std::env;
@@ -33,19 +34,66 @@ struct BinaryEdgeResponse {
 }
 
 impl IntoSubdomain for BinaryEdgeResponse {
-    fn subdomains(&self) -> Vec<String> {
-        self.events.iter().map(|s| s.to_owned()).collect()
+    fn subdomains(&self) -> Vec<Subdomain> {
+        self.events.iter().map(Subdomain::from).collect()
     }
 }
 
-#[derive(Default, Clone)]
+// The most pages fetched for a single domain, each page costs a query credit
+const DEFAULT_MAX_PAGES: i32 = 10;
+// How many pages are fetched at the same time
+const DEFAULT_CONCURRENCY: usize = 4;
+
+#[derive(Clone)]
 pub struct BinaryEdge {
     client: Client,
+    // The maximum number of pages to fetch per domain, `BINARYEDGE_MAX_PAGES` in the environment
+    // or a `.env` file.
+    max_pages: i32,
+    // The number of pages to fetch concurrently, `BINARYEDGE_CONCURRENCY` in the environment or a
+    // `.env` file.
+    concurrency: usize,
+}
+
+impl Default for BinaryEdge {
+    fn default() -> Self {
+        Self {
+            client: Client::default(),
+            max_pages: DEFAULT_MAX_PAGES,
+            concurrency: DEFAULT_CONCURRENCY,
+        }
+    }
 }
 
 impl BinaryEdge {
     pub fn new(client: Client) -> Self {
-        Self { client }
+        dotenv().ok();
+        let max_pages = env::var("BINARYEDGE_MAX_PAGES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MAX_PAGES);
+        let concurrency = env::var("BINARYEDGE_CONCURRENCY")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_CONCURRENCY);
+
+        Self {
+            client,
+            max_pages,
+            concurrency,
+        }
+    }
+
+    /// Sets the maximum number of pages to fetch for each domain.
+    pub fn max_pages(mut self, pages: i32) -> Self {
+        self.max_pages = pages;
+        self
+    }
+
+    /// Sets the number of pages to fetch concurrently.
+    pub fn concurrency(mut self, limit: usize) -> Self {
+        self.concurrency = limit;
+        self
     }
 
     fn build_url(&self, host: &str, page: Option<i32>) -> String {
@@ -60,45 +108,71 @@ impl BinaryEdge {
             ),
         }
     }
-}
 
-//TODO: Clean this up, make pages fetch async
-// fetches the page in sequential order, it would be better to fetch them concurrently,
-// but for the small amount of pages it probably doesn't matter
-#[async_trait]
-impl DataSource for BinaryEdge {
-    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<String>>) -> Result<()> {
-        trace!("fetching data from binaryedge for: {}", &host);
-        let mut tasks = Vec::new();
-        let mut results = Vec::new();
-        let resp = next_page(self.client.clone(), host.clone(), None).await?;
+    // The last page to fetch given the first response, pages start at 1.
+    fn last_page(&self, resp: &BinaryEdgeResponse) -> i32 {
+        if resp.pagesize <= 0 {
+            return resp.page;
+        }
 
-        // insert subdomains from first page.
-        results.extend(resp.subdomains().into_iter());
-        let mut page = resp.page;
+        let pages = (resp.total + resp.pagesize - 1) / resp.pagesize;
+        std::cmp::min(pages, self.max_pages)
+    }
 
-        loop {
-            let host = host.clone();
-            let client = self.client.clone();
+    async fn next_page(
+        &self,
+        token: &str,
+        host: &str,
+        page: Option<i32>,
+    ) -> Result<BinaryEdgeResponse> {
+        trace!("fetching page {:?} from binaryedge for: {}", page, host);
+        let uri = self.build_url(host, page);
+        let resp = self.client.get(&uri).header("X-Key", token).send().await?;
+
+        if resp.status().is_success() {
+            let be: BinaryEdgeResponse = resp.json().await?;
+            return Ok(be);
+        }
 
-            if page > 0 && page * resp.pagesize >= resp.total {
-                break;
-            }
+        info!("binaryedge returned authentication error");
+        Err(VitaError::AuthError("BinaryEdge".into()))
+    }
+}
 
-            page += 1;
-            tasks.push(tokio::task::spawn(async move {
-                next_page(client, host, Some(page)).await
-            }));
+#[async_trait]
+impl DataSource for BinaryEdge {
+    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<Subdomain>>) -> Result<()> {
+        trace!("fetching data from binaryedge for: {}", &host);
+        let token = match Creds::read_creds() {
+            Ok(creds) => creds.token,
+            Err(e) => return Err(e),
+        };
+
+        let first = self.next_page(&token, &host, None).await?;
+        let last_page = self.last_page(&first);
+        let mut total = first.events.len();
+        let subdomains = first.subdomains();
+        if !subdomains.is_empty() {
+            let _ = tx.send(subdomains).await;
         }
 
-        for t in tasks {
-            let subs = t.await??.subdomains().into_iter();
-            results.extend(subs);
+        // Send each page as soon as it arrives, with at most `concurrency` requests in flight.
+        let mut pages = stream::iter(first.page + 1..=last_page)
+            .map(|p| self.next_page(&token, &host, Some(p)))
+            .buffer_unordered(self.concurrency.max(1));
+
+        while let Some(page) = pages.next().await {
+            match page {
+                Ok(resp) => {
+                    total += resp.events.len();
+                    let _ = tx.send(resp.subdomains()).await;
+                }
+                Err(e) => warn!("couldn't fetch a page from binaryedge for {}: {}", &host, e),
+            }
         }
 
-        info!("Discovered {} results for: {}", results.len(), &host);
-        if !results.is_empty() {
-            let _ = tx.send(results).await;
+        if total > 0 {
+            info!("Discovered {} results for: {}", total, &host);
             return Ok(());
         }
 
@@ -107,44 +181,48 @@ impl DataSource for BinaryEdge {
     }
 }
 
-async fn next_page(
-    client: Client,
-    host: Arc<String>,
-    page: Option<i32>,
-) -> Result<BinaryEdgeResponse> {
-    trace!("fetching a page from binaryedge for: {}", &host);
-    let uri = BinaryEdge::default().build_url(&host, page);
-    let token = match Creds::read_creds() {
-        Ok(creds) => creds.token,
-        Err(e) => return Err(e),
-    };
-
-    let resp = client.get(&uri).header("X-Key", token).send().await?;
-
-    if resp.status().is_success() {
-        let be: BinaryEdgeResponse = resp.json().await?;
-        return Ok(be);
-    }
-
-    info!("binaryedge returned authentication error");
-    Err(VitaError::AuthError("BinaryEdge".into()))
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
     use matches::matches;
     use tokio::sync::mpsc::channel;
 
+    fn response(page: i32, total: i32) -> BinaryEdgeResponse {
+        BinaryEdgeResponse {
+            page,
+            pagesize: 100,
+            total,
+            events: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn url_builder() {
+        let correct_uri =
+            "https://api.binaryedge.io/v2/query/domains/subdomain/hackerone.com?page=2";
+        assert_eq!(
+            correct_uri,
+            BinaryEdge::default().build_url("hackerone.com", Some(2))
+        );
+    }
+
+    #[test]
+    fn caps_pages() {
+        let be = BinaryEdge::default().max_pages(5);
+        assert_eq!(be.last_page(&response(1, 50)), 1);
+        assert_eq!(be.last_page(&response(1, 250)), 3);
+        assert_eq!(be.last_page(&response(1, 100_000)), 5);
+    }
+
     // Tests passed locally, ignoring for now.
     #[tokio::test]
     #[ignore]
     async fn returns_results() {
         let (tx, mut rx) = channel(1);
         let host = Arc::new("hackerone.com".to_string());
-        let _ = BinaryEdge::default().run(host, tx).await;
+        tokio::spawn(async move { BinaryEdge::default().run(host, tx).await });
         let mut results = Vec::new();
-        for r in rx.recv().await {
+        while let Some(r) = rx.recv().await {
             results.extend(r)
         }
         assert!(!results.is_empty());
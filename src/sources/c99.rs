@@ -1,5 +1,5 @@
 use crate::error::{Result, VitaError};
-use crate::{DataSource, IntoSubdomain};
+use crate::{DataSource, IntoSubdomain, Subdomain};
 use async_trait::async_trait;
 use dotenv::dotenv;
 use reqwest::Client;
@@ -34,11 +34,11 @@ struct C99Item {
 }
 
 impl IntoSubdomain for C99Result {
-    fn subdomains(&self) -> Vec<String> {
+    fn subdomains(&self) -> Vec<Subdomain> {
         self.subdomains
             .iter()
             .flatten()
-            .map(|s| s.subdomain.to_string())
+            .map(|s| Subdomain::from(s.subdomain.to_string()))
             .collect()
     }
 }
@@ -63,7 +63,7 @@ impl C99 {
 
 #[async_trait]
 impl DataSource for C99 {
-    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<String>>) -> Result<()> {
+    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<Subdomain>>) -> Result<()> {
         trace!("fetching data from C99 for: {}", &host);
         let api_key = match Creds::read_creds() {
             Ok(creds) => creds.key,
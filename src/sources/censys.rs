@@ -0,0 +1,256 @@
+use crate::error::{Result, VitaError};
+use crate::{DataSource, IntoSubdomain, Subdomain};
+use async_trait::async_trait;
+use dotenv::dotenv;
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use std::env;
+use std::sync::Arc;
+use tokio::sync::mpsc::Sender;
+use tracing::{debug, info, trace, warn};
+use url::Url;
+
+const API_URL: &str = "https://search.censys.io/api/v2/certificates/search";
+const PER_PAGE: usize = 100;
+// The most pages fetched for a single domain, every page counts against the monthly quota
+const DEFAULT_MAX_PAGES: usize = 10;
+
+struct Creds {
+    id: String,
+    secret: String,
+}
+
+impl Creds {
+    fn read_creds() -> Result<Self> {
+        dotenv().ok();
+        let id = env::var("CENSYS_API_ID");
+        let secret = env::var("CENSYS_SECRET");
+
+        match (id, secret) {
+            (Ok(id), Ok(secret)) => Ok(Self { id, secret }),
+            _ => Err(VitaError::UnsetKeys(vec![
+                "CENSYS_API_ID".into(),
+                "CENSYS_SECRET".into(),
+            ])),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct Hit {
+    #[serde(default)]
+    names: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct Links {
+    #[serde(default)]
+    next: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct SearchResult {
+    hits: Vec<Hit>,
+    #[serde(default)]
+    links: Links,
+}
+
+#[derive(Deserialize, Debug)]
+struct CensysResponse {
+    result: SearchResult,
+}
+
+impl IntoSubdomain for CensysResponse {
+    fn subdomains(&self) -> Vec<Subdomain> {
+        self.result
+            .hits
+            .iter()
+            .flat_map(|h| h.names.iter())
+            .map(Subdomain::from)
+            .collect()
+    }
+}
+
+impl CensysResponse {
+    // The cursor for the next page, or `None` on the last page.
+    fn next_cursor(&self) -> Option<&str> {
+        match self.result.links.next.as_str() {
+            "" => None,
+            cursor => Some(cursor),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Censys {
+    client: Client,
+    // `CENSYS_MAX_PAGES` in the environment or a `.env` file.
+    max_pages: usize,
+}
+
+impl Default for Censys {
+    fn default() -> Self {
+        Self {
+            client: Client::default(),
+            max_pages: DEFAULT_MAX_PAGES,
+        }
+    }
+}
+
+impl Censys {
+    pub fn new(client: Client) -> Self {
+        dotenv().ok();
+        let max_pages = env::var("CENSYS_MAX_PAGES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MAX_PAGES);
+
+        Self { client, max_pages }
+    }
+
+    /// Sets the maximum number of pages to fetch for each domain.
+    pub fn max_pages(mut self, pages: usize) -> Self {
+        self.max_pages = pages;
+        self
+    }
+
+    fn build_url(&self, host: &str, cursor: Option<&str>) -> String {
+        let query = format!("parsed.names: {}", host);
+        let per_page = PER_PAGE.to_string();
+        let mut params = vec![("q", query.as_str()), ("per_page", per_page.as_str())];
+        if let Some(c) = cursor {
+            params.push(("cursor", c));
+        }
+
+        Url::parse_with_params(API_URL, &params)
+            .unwrap()
+            .to_string()
+    }
+
+    async fn search(&self, creds: &Creds, uri: &str) -> Result<CensysResponse> {
+        let resp = self
+            .client
+            .get(uri)
+            .basic_auth(&creds.id, Some(&creds.secret))
+            .send()
+            .await?;
+
+        match resp.status() {
+            StatusCode::TOO_MANY_REQUESTS => Err(VitaError::QuotaExceeded("Censys".into())),
+            s if s.is_client_error() => {
+                warn!("got status: {} from censys", s.as_str());
+                Err(VitaError::AuthError("Censys".into()))
+            }
+            _ => Ok(resp.json().await?),
+        }
+    }
+}
+
+#[async_trait]
+impl DataSource for Censys {
+    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<Subdomain>>) -> Result<()> {
+        trace!("fetching data from censys for: {}", &host);
+        let creds = match Creds::read_creds() {
+            Ok(c) => c,
+            Err(e) => return Err(e),
+        };
+
+        let mut total = 0;
+        let mut cursor: Option<String> = None;
+        for page in 0..self.max_pages {
+            let uri = self.build_url(&host, cursor.as_deref());
+            let resp = match self.search(&creds, &uri).await {
+                Ok(resp) => resp,
+                // Keep what we've already sent if a later page fails
+                Err(e) if total > 0 => {
+                    warn!("couldn't fetch page {} from censys: {}", page, e);
+                    break;
+                }
+                Err(e) => return Err(e),
+            };
+
+            let subdomains = resp.subdomains();
+            debug!("censys page {} had {} results", page, subdomains.len());
+            if !subdomains.is_empty() {
+                total += subdomains.len();
+                let _ = tx.send(subdomains).await;
+            }
+
+            match resp.next_cursor() {
+                Some(next) => cursor = Some(next.to_string()),
+                None => break,
+            }
+        }
+
+        if total > 0 {
+            info!("Discovered {} results for: {}", total, &host);
+            return Ok(());
+        }
+
+        warn!("no results for {} from Censys", &host);
+        Err(VitaError::SourceError("Censys".into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use matches::matches;
+    use tokio::sync::mpsc::channel;
+
+    #[test]
+    fn url_builder() {
+        let censys = Censys::default();
+        let correct_uri = "https://search.censys.io/api/v2/certificates/search?q=parsed.names%3A+hackerone.com&per_page=100";
+        assert_eq!(correct_uri, censys.build_url("hackerone.com", None));
+
+        let correct_uri = "https://search.censys.io/api/v2/certificates/search?q=parsed.names%3A+hackerone.com&per_page=100&cursor=eyJhZnRlciI6WzFdfQ%3D%3D";
+        assert_eq!(
+            correct_uri,
+            censys.build_url("hackerone.com", Some("eyJhZnRlciI6WzFdfQ=="))
+        );
+    }
+
+    #[test]
+    fn follows_cursor() {
+        let resp: CensysResponse = serde_json::from_str(
+            r#"{"code": 200, "status": "OK", "result": {"total": 2, "hits": [
+                {"names": ["hackerone.com", "www.hackerone.com"]},
+                {"names": ["api.hackerone.com"]}
+            ], "links": {"prev": "", "next": "eyJhZnRlciI6WzFdfQ=="}}}"#,
+        )
+        .unwrap();
+        assert_eq!(resp.subdomains().len(), 3);
+        assert_eq!(resp.next_cursor(), Some("eyJhZnRlciI6WzFdfQ=="));
+
+        let last: CensysResponse = serde_json::from_str(
+            r#"{"result": {"hits": [], "links": {"prev": "abc", "next": ""}}}"#,
+        )
+        .unwrap();
+        assert_eq!(last.next_cursor(), None);
+    }
+
+    #[ignore]
+    #[tokio::test]
+    async fn returns_results() {
+        let (tx, mut rx) = channel(1);
+        let host = Arc::new("hackerone.com".to_owned());
+        tokio::spawn(async move { Censys::default().run(host, tx).await });
+        let mut results = Vec::new();
+        while let Some(r) = rx.recv().await {
+            results.extend(r)
+        }
+        assert!(!results.is_empty());
+    }
+
+    #[ignore]
+    #[tokio::test]
+    async fn handle_no_results() {
+        let (tx, _rx) = channel(1);
+        let host = Arc::new("anVubmxpa2VzdGVh.com".to_string());
+        assert!(matches!(
+            Censys::default().run(host, tx).await.err().unwrap(),
+            VitaError::SourceError(_)
+        ));
+    }
+}
@@ -1,57 +1,151 @@
 use crate::error::{Result, VitaError};
-use crate::{DataSource, IntoSubdomain};
+use crate::{DataSource, IntoSubdomain, Subdomain};
 use async_trait::async_trait;
+use dotenv::dotenv;
 use reqwest::Client;
 use serde::Deserialize;
+use std::env;
 use std::sync::Arc;
 use tokio::sync::mpsc::Sender;
-use tracing::{info, trace, warn};
+use tracing::{debug, info, trace, warn};
+
+// The most pages followed for a single domain, large organisations can have thousands
+const DEFAULT_MAX_PAGES: usize = 20;
+
+// The key is optional, but anonymous use of the api is heavily rate limited.
+struct Creds {
+    api_key: String,
+}
+
+impl Creds {
+    pub fn read_creds() -> Result<Self> {
+        dotenv().ok();
+        match env::var("CERTSPOTTER_KEY") {
+            Ok(api_key) => Ok(Self { api_key }),
+            Err(_) => Err(VitaError::UnsetKeys(vec!["CERTSPOTTER_KEY".into()])),
+        }
+    }
+}
 
 #[derive(Debug, Deserialize)]
 struct CertSpotterResult {
+    id: String,
     dns_names: Vec<String>,
 }
 
 impl IntoSubdomain for Vec<CertSpotterResult> {
-    fn subdomains(&self) -> Vec<String> {
-        self.iter().flat_map(|d| d.dns_names.to_owned()).collect()
+    fn subdomains(&self) -> Vec<Subdomain> {
+        self.iter()
+            .flat_map(|d| d.dns_names.iter().map(Subdomain::from))
+            .collect()
     }
 }
 
-#[derive(Default, Clone)]
+#[derive(Clone)]
 pub struct CertSpotter {
     client: Client,
+    // `CERTSPOTTER_MAX_PAGES` in the environment or a `.env` file.
+    max_pages: usize,
+}
+
+impl Default for CertSpotter {
+    fn default() -> Self {
+        Self {
+            client: Client::default(),
+            max_pages: DEFAULT_MAX_PAGES,
+        }
+    }
 }
 
 impl CertSpotter {
     pub fn new(client: Client) -> Self {
-        Self { client }
+        dotenv().ok();
+        let max_pages = env::var("CERTSPOTTER_MAX_PAGES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MAX_PAGES);
+
+        Self { client, max_pages }
     }
 
-    fn build_url(&self, host: &str) -> String {
-        format!(
-            "https://api.certspotter.com/v1/issuances?domain={}\
+    /// Sets the maximum number of pages to fetch for each domain.
+    pub fn max_pages(mut self, pages: usize) -> Self {
+        self.max_pages = pages;
+        self
+    }
+
+    // `after` is the id of the last issuance on the previous page.
+    fn build_url(&self, host: &str, after: Option<&str>) -> String {
+        match after {
+            Some(id) => format!(
+                "https://api.certspotter.com/v1/issuances?domain={}\
+        &include_subdomains=true&expand=dns_names&after={}",
+                host, id
+            ),
+            None => format!(
+                "https://api.certspotter.com/v1/issuances?domain={}\
         &include_subdomains=true&expand=dns_names",
-            host
-        )
+                host
+            ),
+        }
     }
 }
 
 #[async_trait]
 impl DataSource for CertSpotter {
-    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<String>>) -> Result<()> {
+    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<Subdomain>>) -> Result<()> {
         trace!("fetching data from certspotter for: {}", &host);
-        let uri = self.build_url(&host);
-        let resp: Option<Vec<CertSpotterResult>> =
-            self.client.get(&uri).send().await?.json().await?;
-
-        if let Some(data) = resp {
-            let subdomains = data.subdomains();
-            if !subdomains.is_empty() {
-                info!("Discovered {} results for: {}", &subdomains.len(), &host);
-                let _ = tx.send(subdomains).await;
-                return Ok(());
+        let api_key = Creds::read_creds().ok().map(|c| c.api_key);
+        let mut after: Option<String> = None;
+        let mut total = 0;
+        let mut pages = 0;
+
+        for _ in 0..self.max_pages {
+            let uri = self.build_url(&host, after.as_deref());
+            let mut req = self.client.get(&uri);
+            if let Some(key) = &api_key {
+                req = req.bearer_auth(key);
             }
+
+            let resp = req.send().await?;
+            if !resp.status().is_success() {
+                warn!("got status: {} from certspotter", resp.status().as_str());
+                if total == 0 && resp.status().is_client_error() {
+                    return Err(VitaError::AuthError("CertSpotter".into()));
+                }
+                break;
+            }
+
+            let page: Vec<CertSpotterResult> = resp.json().await?;
+            // an empty page means we've followed the cursor to the end
+            let last = match page.last() {
+                Some(issuance) => issuance.id.to_owned(),
+                None => break,
+            };
+
+            let subdomains = page.subdomains();
+            debug!(
+                "certspotter page after {:?} had {} results",
+                &after,
+                subdomains.len()
+            );
+            total += subdomains.len();
+            let _ = tx.send(subdomains).await;
+            after = Some(last);
+            pages += 1;
+        }
+
+        // Every page had issuances, so the cursor may still have more after the last one
+        if pages > 0 && pages == self.max_pages {
+            warn!(
+                "stopped paging certspotter for {} at the {} page cap, set CERTSPOTTER_MAX_PAGES to fetch more",
+                &host, self.max_pages
+            );
+        }
+
+        if total > 0 {
+            info!("Discovered {} results for: {}", total, &host);
+            return Ok(());
         }
 
         warn!("no results for {} from CertSpotter", &host);
@@ -71,7 +165,17 @@ mod tests {
         &include_subdomains=true&expand=dns_names";
         assert_eq!(
             correct_uri,
-            CertSpotter::default().build_url("hackerone.com")
+            CertSpotter::default().build_url("hackerone.com", None)
+        );
+    }
+
+    #[test]
+    fn url_builder_after() {
+        let correct_uri = "https://api.certspotter.com/v1/issuances?domain=hackerone.com\
+        &include_subdomains=true&expand=dns_names&after=1234";
+        assert_eq!(
+            correct_uri,
+            CertSpotter::default().build_url("hackerone.com", Some("1234"))
         );
     }
 
@@ -80,9 +184,10 @@ mod tests {
     async fn returns_results() {
         let (tx, mut rx) = channel(1);
         let host = Arc::new("hackerone.com".to_owned());
-        let _ = CertSpotter::default().run(host, tx).await;
+        // Every page is sent separately, so the results are read while it runs
+        tokio::spawn(async move { CertSpotter::default().max_pages(3).run(host, tx).await });
         let mut results = Vec::new();
-        for r in rx.recv().await {
+        while let Some(r) = rx.recv().await {
             results.extend(r)
         }
         assert!(!results.is_empty());
@@ -1,14 +1,22 @@
 use crate::error::{Result, VitaError};
-use crate::{DataSource, IntoSubdomain};
+use crate::{DataSource, IntoSubdomain, Subdomain};
 use async_trait::async_trait;
 use dotenv::dotenv;
 use reqwest::header::AUTHORIZATION;
 use reqwest::Client;
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::env;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
 use tokio::sync::mpsc::Sender;
-use tracing::{info, trace, warn};
+use tokio::sync::Mutex;
+use tokio::task;
+use tracing::{debug, info, trace, warn};
+use zip::ZipArchive;
 
 struct Creds {
     key: String,
@@ -31,33 +39,250 @@ struct ChaosResult {
 }
 
 impl IntoSubdomain for ChaosResult {
-    fn subdomains(&self) -> Vec<String> {
+    fn subdomains(&self) -> Vec<Subdomain> {
         self.subdomains
             .iter()
-            .map(|s| format!("{}.{}", s, self.domain))
+            .map(|s| Subdomain::from(format!("{}.{}", s, self.domain)))
             .collect()
     }
 }
 
-#[derive(Default, Clone)]
+// The list of programs in the public Chaos dataset along with where to download their archives
+const INDEX_URL: &str = "https://chaos-data.projectdiscovery.io/index.json";
+const INDEX_FILE: &str = "index.json";
+
+#[derive(Deserialize, Debug)]
+struct Program {
+    name: String,
+    #[serde(rename = "URL")]
+    url: String,
+}
+
+impl Program {
+    // The archive is stored in the cache directory under the same name it's published with.
+    fn file_name(&self) -> &str {
+        self.url.rsplit('/').next().unwrap_or(&self.url)
+    }
+}
+
+// Where each domain's list of subdomains lives in the archives, keyed by domain name. Each
+// archive holds a `<domain>.txt` file for every domain in the program.
+#[derive(Debug, Default)]
+struct ArchiveIndex {
+    domains: HashMap<String, Vec<(PathBuf, String)>>,
+}
+
+impl ArchiveIndex {
+    // Reads the index from the directory and lists the files in each archive. Archives which
+    // haven't been downloaded are skipped.
+    fn load(dir: &Path) -> Result<Self> {
+        let programs: Vec<Program> =
+            serde_json::from_str(&fs::read_to_string(dir.join(INDEX_FILE))?)?;
+        let mut index = Self::default();
+
+        for program in programs.iter() {
+            let path = dir.join(program.file_name());
+            if !path.exists() {
+                debug!("no chaos archive for {} at {:?}", &program.name, &path);
+                continue;
+            }
+
+            let archive = ZipArchive::new(File::open(&path)?)?;
+            for name in archive.file_names() {
+                if let Some(domain) = name.rsplit('/').next().and_then(|n| n.strip_suffix(".txt")) {
+                    index
+                        .domains
+                        .entry(domain.to_lowercase())
+                        .or_insert_with(Vec::new)
+                        .push((path.clone(), name.to_string()));
+                }
+            }
+        }
+
+        debug!(
+            "loaded {} domains from the chaos archives",
+            index.domains.len()
+        );
+        Ok(index)
+    }
+
+    // Returns the subdomains of `host` from the archives. The host might itself be a subdomain,
+    // so we look for the closest parent domain which has a list.
+    fn subdomains(&self, host: &str) -> Result<Vec<Subdomain>> {
+        let host = host.to_lowercase();
+        let suffix = format!(".{}", host);
+        let mut labels = host.as_str();
+        let locations = loop {
+            if let Some(locations) = self.domains.get(labels) {
+                break locations;
+            }
+
+            match labels.find('.') {
+                Some(i) => labels = &labels[i + 1..],
+                None => return Ok(Vec::new()),
+            }
+        };
+
+        let mut results = Vec::new();
+        for (path, name) in locations.iter() {
+            let mut archive = ZipArchive::new(File::open(path)?)?;
+            let file = BufReader::new(archive.by_name(name)?);
+            for line in file.lines() {
+                let line = line?;
+                let line = line.trim();
+                if line == host || line.ends_with(&suffix) {
+                    results.push(Subdomain::from(line));
+                }
+            }
+        }
+
+        Ok(results)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Mode {
+    // Query the api with `CHAOS_KEY`
+    Api,
+    // Read the public dataset from a directory, downloading anything missing if asked to
+    Archive { dir: PathBuf, download: bool },
+}
+
+#[derive(Clone)]
 pub struct Chaos {
     client: Client,
+    mode: Mode,
+    // Loaded on first use and shared between every host.
+    archive: Arc<Mutex<Option<Arc<ArchiveIndex>>>>,
+}
+
+impl Default for Chaos {
+    fn default() -> Self {
+        Self {
+            client: Client::default(),
+            mode: Mode::Api,
+            archive: Arc::new(Mutex::new(None)),
+        }
+    }
 }
 
 impl Chaos {
     pub fn new(client: Client) -> Self {
-        Self { client }
+        dotenv().ok();
+        let mode = match env::var("CHAOS_ARCHIVE_DIR") {
+            Ok(dir) => Mode::Archive {
+                dir: dir.into(),
+                download: env::var("CHAOS_DOWNLOAD")
+                    .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+                    .unwrap_or(false),
+            },
+            Err(_) => Mode::Api,
+        };
+
+        Self {
+            client,
+            mode,
+            archive: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Reads subdomains from the public Chaos dataset in `dir` instead of the api, so no key is
+    /// needed. The directory holds the `index.json` and the program archives. If `download` is
+    /// true anything missing is downloaded into the directory first.
+    pub fn archive<P: Into<PathBuf>>(mut self, dir: P, download: bool) -> Self {
+        self.mode = Mode::Archive {
+            dir: dir.into(),
+            download,
+        };
+        self
+    }
+
+    /// Whether the source reads from the public dataset and can be used without a key.
+    pub fn is_archive(&self) -> bool {
+        matches!(self.mode, Mode::Archive { .. })
     }
 
     fn build_url(&self, host: &str) -> String {
         format!("https://dns.projectdiscovery.io/dns/{}/subdomains", host)
     }
-}
 
-#[async_trait]
-impl DataSource for Chaos {
-    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<String>>) -> Result<()> {
-        trace!("fetching data from projectdiscovery choas for: {}", &host);
+    // Streams a file to disk. It's written under a temporary name first, so an interrupted
+    // download isn't mistaken for a complete one on the next run.
+    async fn save(&self, url: &str, path: &Path) -> Result<()> {
+        let mut resp = self.client.get(url).send().await?;
+        if !resp.status().is_success() {
+            warn!("got status: {} downloading {}", resp.status().as_str(), url);
+            return Err(VitaError::SourceError("Chaos".into()));
+        }
+
+        let mut part = path.as_os_str().to_owned();
+        part.push(".part");
+        let mut file = tokio::fs::File::create(&part).await?;
+        while let Some(chunk) = resp.chunk().await? {
+            file.write_all(&chunk).await?;
+        }
+        file.flush().await?;
+        tokio::fs::rename(&part, path).await?;
+        Ok(())
+    }
+
+    // Downloads the index and any archives which aren't already in the directory.
+    async fn download(&self, dir: &Path) -> Result<()> {
+        tokio::fs::create_dir_all(dir).await?;
+        let index_path = dir.join(INDEX_FILE);
+        if tokio::fs::metadata(&index_path).await.is_err() {
+            info!("downloading the chaos index to {:?}", &index_path);
+            self.save(INDEX_URL, &index_path).await?;
+        }
+
+        let index = tokio::fs::read_to_string(&index_path).await?;
+        let programs: Vec<Program> = serde_json::from_str(&index)?;
+        for program in programs.iter() {
+            let path = dir.join(program.file_name());
+            if tokio::fs::metadata(&path).await.is_ok() {
+                continue;
+            }
+
+            debug!("downloading the chaos archive for {}", &program.name);
+            if let Err(e) = self.save(&program.url, &path).await {
+                warn!(
+                    "couldn't download chaos archive for {}: {}",
+                    &program.name, e
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    // Returns the archive index, loading it the first time it's needed.
+    async fn archive_index(&self, dir: &Path, download: bool) -> Result<Arc<ArchiveIndex>> {
+        let mut archive = self.archive.lock().await;
+        if let Some(index) = archive.as_ref() {
+            return Ok(Arc::clone(index));
+        }
+
+        if download {
+            self.download(dir).await?;
+        }
+
+        let dir = dir.to_path_buf();
+        let index = Arc::new(task::spawn_blocking(move || ArchiveIndex::load(&dir)).await??);
+        *archive = Some(Arc::clone(&index));
+        Ok(index)
+    }
+
+    async fn run_archive(
+        &self,
+        host: Arc<String>,
+        dir: &Path,
+        download: bool,
+    ) -> Result<Vec<Subdomain>> {
+        let index = self.archive_index(dir, download).await?;
+        task::spawn_blocking(move || index.subdomains(&host)).await?
+    }
+
+    async fn run_api(&self, host: Arc<String>) -> Result<Vec<Subdomain>> {
         let api_key = match Creds::read_creds() {
             Ok(creds) => creds.key,
             Err(e) => return Err(e),
@@ -73,14 +298,28 @@ impl DataSource for Chaos {
         if resp.status().is_client_error() {
             warn!("got status: {} from chaos", resp.status().as_str());
             return Err(VitaError::AuthError("Chaos".into()));
-        } else {
-            let resp: ChaosResult = resp.json().await?;
-            let subdomains = resp.subdomains();
-            if !subdomains.is_empty() {
-                info!("Discovered {} results for: {}", &subdomains.len(), &host);
-                let _ = tx.send(subdomains).await;
-                return Ok(());
+        }
+
+        let resp: ChaosResult = resp.json().await?;
+        Ok(resp.subdomains())
+    }
+}
+
+#[async_trait]
+impl DataSource for Chaos {
+    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<Subdomain>>) -> Result<()> {
+        trace!("fetching data from projectdiscovery choas for: {}", &host);
+        let subdomains = match &self.mode {
+            Mode::Api => self.run_api(host.clone()).await?,
+            Mode::Archive { dir, download } => {
+                self.run_archive(host.clone(), dir, *download).await?
             }
+        };
+
+        if !subdomains.is_empty() {
+            info!("Discovered {} results for: {}", &subdomains.len(), &host);
+            let _ = tx.send(subdomains).await;
+            return Ok(());
         }
 
         warn!("no results for {} from Chaos", &host);
@@ -94,6 +333,69 @@ mod tests {
     use matches::matches;
     use tokio::sync::mpsc::channel;
 
+    fn fixtures() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/chaos")
+    }
+
+    fn hosts(results: Vec<Subdomain>) -> Vec<String> {
+        let mut hosts: Vec<String> = results.into_iter().map(|s| s.host).collect();
+        hosts.sort();
+        hosts
+    }
+
+    #[test]
+    fn loads_archive_index() {
+        let index = ArchiveIndex::load(&fixtures()).unwrap();
+        let mut domains: Vec<&String> = index.domains.keys().collect();
+        domains.sort();
+        assert_eq!(
+            domains,
+            vec!["hackerone.com", "hackerone.net", "starbucks.com"]
+        );
+    }
+
+    #[test]
+    fn archive_subdomains() {
+        let index = ArchiveIndex::load(&fixtures()).unwrap();
+        assert_eq!(
+            hosts(index.subdomains("hackerone.com").unwrap()),
+            vec![
+                "api.hackerone.com",
+                "docs.hackerone.com",
+                "v2.api.hackerone.com"
+            ]
+        );
+        // Subdomains are looked up in their parent domain's list
+        assert_eq!(
+            hosts(index.subdomains("api.hackerone.com").unwrap()),
+            vec!["api.hackerone.com", "v2.api.hackerone.com"]
+        );
+        assert!(index.subdomains("anVubmxpa2VzdGVh.com").unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn returns_archive_results() {
+        let (tx, mut rx) = channel(1);
+        let host = Arc::new("starbucks.com".to_owned());
+        let chaos = Chaos::default().archive(fixtures(), false);
+        assert!(chaos.run(host, tx).await.is_ok());
+        assert_eq!(
+            hosts(rx.recv().await.unwrap()),
+            vec!["app.starbucks.com", "www.starbucks.com"]
+        );
+    }
+
+    #[tokio::test]
+    async fn handle_no_archive_results() {
+        let (tx, _rx) = channel(1);
+        let host = Arc::new("anVubmxpa2VzdGVh.com".to_string());
+        let chaos = Chaos::default().archive(fixtures(), false);
+        assert!(matches!(
+            chaos.run(host, tx).await.err().unwrap(),
+            VitaError::SourceError(_)
+        ));
+    }
+
     // Ignore, passed locally.
     #[ignore]
     #[tokio::test]
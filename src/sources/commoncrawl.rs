@@ -0,0 +1,291 @@
+use crate::error::{Result, VitaError};
+use crate::sources::wayback::host_from_url;
+use crate::{DataSource, Subdomain};
+use async_trait::async_trait;
+use dotenv::dotenv;
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::env;
+use std::sync::Arc;
+use tokio::sync::mpsc::Sender;
+use tokio::sync::Mutex;
+use tracing::{debug, info, trace, warn};
+
+const COLLINFO_URL: &str = "https://index.commoncrawl.org/collinfo.json";
+// The number of recent crawls to search
+const DEFAULT_CRAWLS: usize = 3;
+// The most pages read from each crawl for a single domain, every page holds up to 15000 captures
+const DEFAULT_MAX_PAGES: usize = 5;
+
+// A crawl from `collinfo.json`, which lists the newest crawl first.
+#[derive(Deserialize, Debug)]
+struct Crawl {
+    id: String,
+    #[serde(rename = "cdx-api")]
+    cdx_api: String,
+}
+
+// The number of pages of captures the index api has for a query.
+#[derive(Deserialize, Debug)]
+struct PageCount {
+    pages: usize,
+}
+
+// A line of the CDX index api's json output.
+#[derive(Deserialize, Debug)]
+struct Capture {
+    url: String,
+}
+
+// The index api returns one json object per line, this returns the unique hosts from them.
+fn hosts_from_captures(body: &str) -> Vec<Subdomain> {
+    let hosts: HashSet<Subdomain> = body
+        .lines()
+        .filter_map(|l| serde_json::from_str::<Capture>(l).ok())
+        .filter_map(|c| host_from_url(&c.url))
+        .collect();
+
+    hosts.into_iter().collect()
+}
+
+#[derive(Clone)]
+pub struct CommonCrawl {
+    client: Client,
+    // The number of recent crawls to search, `COMMONCRAWL_CRAWLS` in the environment or a `.env`
+    // file.
+    crawls: usize,
+    // `COMMONCRAWL_MAX_PAGES` in the environment or a `.env` file.
+    max_pages: usize,
+    // The index api urls of the crawls to search, fetched once and shared between every host.
+    indexes: Arc<Mutex<Option<Arc<Vec<String>>>>>,
+}
+
+impl Default for CommonCrawl {
+    fn default() -> Self {
+        Self {
+            client: Client::default(),
+            crawls: DEFAULT_CRAWLS,
+            max_pages: DEFAULT_MAX_PAGES,
+            indexes: Arc::new(Mutex::new(None)),
+        }
+    }
+}
+
+impl CommonCrawl {
+    pub fn new(client: Client) -> Self {
+        dotenv().ok();
+        let crawls = env::var("COMMONCRAWL_CRAWLS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_CRAWLS);
+        let max_pages = env::var("COMMONCRAWL_MAX_PAGES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MAX_PAGES);
+
+        Self {
+            client,
+            crawls,
+            max_pages,
+            indexes: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Sets the number of recent crawls to search.
+    pub fn crawls(mut self, crawls: usize) -> Self {
+        self.crawls = crawls;
+        self
+    }
+
+    /// Sets the maximum number of pages to read from each crawl for each domain.
+    pub fn max_pages(mut self, pages: usize) -> Self {
+        self.max_pages = pages;
+        self
+    }
+
+    fn build_url(&self, cdx_api: &str, host: &str, page: usize) -> String {
+        format!(
+            "{}?url=*.{}&output=json&fl=url&page={}",
+            cdx_api, host, page
+        )
+    }
+
+    fn pages_url(&self, cdx_api: &str, host: &str) -> String {
+        format!("{}?url=*.{}&output=json&showNumPages=true", cdx_api, host)
+    }
+
+    // Returns the index api urls for the most recent crawls, fetching them the first time
+    // they're needed.
+    async fn indexes(&self) -> Result<Arc<Vec<String>>> {
+        let mut indexes = self.indexes.lock().await;
+        if let Some(urls) = indexes.as_ref() {
+            return Ok(Arc::clone(urls));
+        }
+
+        let crawls: Vec<Crawl> = self.client.get(COLLINFO_URL).send().await?.json().await?;
+        let urls: Vec<String> = crawls
+            .into_iter()
+            .take(self.crawls)
+            .map(|c| {
+                debug!("searching commoncrawl crawl {}", &c.id);
+                c.cdx_api
+            })
+            .collect();
+
+        let urls = Arc::new(urls);
+        *indexes = Some(Arc::clone(&urls));
+        Ok(urls)
+    }
+
+    // Returns how many pages of captures the crawl has for the host.
+    async fn pages(&self, cdx_api: &str, host: &str) -> Result<usize> {
+        let resp = self
+            .client
+            .get(&self.pages_url(cdx_api, host))
+            .send()
+            .await?;
+        match resp.status() {
+            StatusCode::NOT_FOUND => Ok(0),
+            s if !s.is_success() => {
+                warn!("got status: {} from commoncrawl", s.as_str());
+                Err(VitaError::SourceError("CommonCrawl".into()))
+            }
+            _ => Ok(resp.json::<PageCount>().await?.pages),
+        }
+    }
+
+    async fn search(&self, cdx_api: &str, host: &str, page: usize) -> Result<Vec<Subdomain>> {
+        let uri = self.build_url(cdx_api, host, page);
+        let resp = self.client.get(&uri).send().await?;
+
+        // The index api returns a 404 when there aren't any captures
+        match resp.status() {
+            StatusCode::NOT_FOUND => Ok(Vec::new()),
+            s if !s.is_success() => {
+                warn!("got status: {} from commoncrawl", s.as_str());
+                Err(VitaError::SourceError("CommonCrawl".into()))
+            }
+            _ => Ok(hosts_from_captures(&resp.text().await?)),
+        }
+    }
+}
+
+#[async_trait]
+impl DataSource for CommonCrawl {
+    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<Subdomain>>) -> Result<()> {
+        trace!("fetching data from commoncrawl for: {}", &host);
+        let indexes = self.indexes().await?;
+        let mut seen = HashSet::new();
+
+        for cdx_api in indexes.iter() {
+            let pages = match self.pages(cdx_api, &host).await {
+                Ok(pages) => pages,
+                Err(e) => {
+                    warn!("couldn't search {} for {}: {}", cdx_api, &host, e);
+                    continue;
+                }
+            };
+            if pages > self.max_pages {
+                debug!(
+                    "only reading {} of the {} pages in {} for {}",
+                    self.max_pages, pages, cdx_api, &host
+                );
+            }
+
+            for page in 0..std::cmp::min(pages, self.max_pages) {
+                match self.search(cdx_api, &host, page).await {
+                    Ok(subdomains) => {
+                        let subdomains: Vec<Subdomain> = subdomains
+                            .into_iter()
+                            .filter(|s| seen.insert(s.host.clone()))
+                            .collect();
+                        if !subdomains.is_empty() {
+                            let _ = tx.send(subdomains).await;
+                        }
+                    }
+                    Err(e) => {
+                        warn!("couldn't search {} for {}: {}", cdx_api, &host, e);
+                        break;
+                    }
+                }
+            }
+        }
+
+        if !seen.is_empty() {
+            info!("Discovered {} results for: {}", seen.len(), &host);
+            return Ok(());
+        }
+
+        warn!("no results for {} from CommonCrawl", &host);
+        Err(VitaError::SourceError("CommonCrawl".into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use matches::matches;
+    use tokio::sync::mpsc::channel;
+
+    #[test]
+    fn url_builder() {
+        let cdx_api = "https://index.commoncrawl.org/CC-MAIN-2020-45-index";
+        let correct_uri =
+            "https://index.commoncrawl.org/CC-MAIN-2020-45-index?url=*.hackerone.com&output=json&fl=url&page=2";
+        assert_eq!(
+            correct_uri,
+            CommonCrawl::default().build_url(cdx_api, "hackerone.com", 2)
+        );
+
+        let correct_uri =
+            "https://index.commoncrawl.org/CC-MAIN-2020-45-index?url=*.hackerone.com&output=json&showNumPages=true";
+        assert_eq!(
+            correct_uri,
+            CommonCrawl::default().pages_url(cdx_api, "hackerone.com")
+        );
+    }
+
+    #[test]
+    fn parses_captures() {
+        let body = r#"{"url": "https://api.hackerone.com/v1/me"}
+{"url": "https://api.hackerone.com/docs"}
+{"url": "http://www.hackerone.com/"}
+not json"#;
+        let mut hosts: Vec<String> = hosts_from_captures(body)
+            .into_iter()
+            .map(|s| s.host)
+            .collect();
+        hosts.sort();
+        assert_eq!(hosts, vec!["api.hackerone.com", "www.hackerone.com"]);
+
+        let count: PageCount =
+            serde_json::from_str(r#"{"pages": 4, "pageSize": 5, "blocks": 17}"#).unwrap();
+        assert_eq!(count.pages, 4);
+    }
+
+    #[ignore]
+    #[tokio::test]
+    async fn returns_results() {
+        let (tx, mut rx) = channel(1);
+        let host = Arc::new("hackerone.com".to_owned());
+        // Every page is sent separately, so the results are read while it runs
+        tokio::spawn(async move { CommonCrawl::default().max_pages(2).run(host, tx).await });
+        let mut results = Vec::new();
+        while let Some(r) = rx.recv().await {
+            results.extend(r)
+        }
+        assert!(!results.is_empty());
+    }
+
+    #[ignore]
+    #[tokio::test]
+    async fn handle_no_results() {
+        let (tx, _rx) = channel(3);
+        let host = Arc::new("anVubmxpa2VzdGVh.com".to_string());
+        assert!(matches!(
+            CommonCrawl::default().run(host, tx).await.err().unwrap(),
+            VitaError::SourceError(_)
+        ));
+    }
+}
@@ -1,52 +1,332 @@
 use crate::error::{Result, VitaError};
-use crate::{DataSource, IntoSubdomain};
+use crate::{DataSource, IntoSubdomain, Subdomain, QUEUE_SIZE};
 use async_trait::async_trait;
+use dotenv::dotenv;
 use reqwest::Client;
 use serde::Deserialize;
+use std::collections::HashSet;
+use std::env;
 use std::sync::Arc;
 use tokio::sync::mpsc::Sender;
-use tracing::{info, trace, warn};
+use tokio::sync::Mutex;
+use tokio_postgres::NoTls;
+use tracing::{debug, info, trace, warn};
+
+// crt.sh's public read-only database
+const CRTSH_POSTGRES: &str = "host=crt.sh port=5432 user=guest dbname=certwatch";
+
+// Identity search over every certificate which has a name ending in the host.
+const IDENTITY_QUERY: &str = "SELECT DISTINCT cai.name_value \
+    FROM certificate_and_identities cai \
+    WHERE plainto_tsquery('certwatch', $1) @@ identities(cai.certificate) \
+    AND cai.name_value ILIKE ('%.' || $1)";
+
+// Appended to `IDENTITY_QUERY` when expired certificates should be skipped.
+const NOT_EXPIRED: &str = " AND coalesce(x509_notAfter(cai.certificate), 'infinity'::timestamp) \
+    >= now() AT TIME ZONE 'UTC'";
+
+/// How the crt.sh data is queried
+#[derive(Clone, Debug, PartialEq)]
+enum Mode {
+    /// The JSON output of the crt.sh website
+    Http,
+    /// A postgres database with the crt.sh schema, using the given connection string
+    Postgres(String),
+}
+
+// Options for the crt.sh source, read from the environment or a `.env` file.
+#[derive(Clone)]
+struct Config {
+    mode: Mode,
+    // Skip certificates which have already expired
+    exclude_expired: bool,
+    // Skip rows for certificate ids we've already processed, the database mode already
+    // returns distinct names.
+    dedup: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            mode: Mode::Http,
+            exclude_expired: false,
+            dedup: true,
+        }
+    }
+}
+
+impl Config {
+    fn from_env() -> Self {
+        dotenv().ok();
+        let mut config = Self::default();
+        if let Ok(mode) = env::var("CRTSH_MODE") {
+            if mode.eq_ignore_ascii_case("postgres") {
+                let conn = env::var("CRTSH_POSTGRES_URL").unwrap_or_else(|_| CRTSH_POSTGRES.into());
+                config.mode = Mode::Postgres(conn);
+            }
+        }
+        if let Ok(v) = env::var("CRTSH_EXCLUDE_EXPIRED") {
+            config.exclude_expired = v == "1" || v.eq_ignore_ascii_case("true");
+        }
+        if let Ok(v) = env::var("CRTSH_DEDUP") {
+            config.dedup = v == "1" || v.eq_ignore_ascii_case("true");
+        }
+        config
+    }
+}
 
 #[derive(Deserialize, Hash, PartialEq, Debug, Eq)]
 struct CrtshResult {
+    id: Option<u64>,
+    #[serde(default)]
+    common_name: String,
     name_value: String,
 }
 
-impl IntoSubdomain for Vec<CrtshResult> {
-    fn subdomains(&self) -> Vec<String> {
-        self.iter().map(|s| s.name_value.to_owned()).collect()
+// `name_value` holds every identity on the certificate separated by newlines, and the
+// `common_name` is usually, but not always, one of them.
+impl IntoSubdomain for CrtshResult {
+    fn subdomains(&self) -> Vec<Subdomain> {
+        let mut names: Vec<String> = self
+            .name_value
+            .lines()
+            .chain(std::iter::once(self.common_name.as_str()))
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_owned)
+            .collect();
+        names.sort();
+        names.dedup();
+        names.into_iter().map(Subdomain::from).collect()
+    }
+}
+
+// Entries without an id can't be told apart, so they're never treated as duplicates.
+fn is_duplicate(seen: &mut HashSet<u64>, cert: &CrtshResult) -> bool {
+    matches!(cert.id, Some(id) if !seen.insert(id))
+}
+
+/// Splits a JSON array of objects into its elements as the bytes arrive, so we don't have to
+/// hold the entire crt.sh response in memory before we can start sending results.
+#[derive(Default)]
+struct ArraySplitter {
+    buf: Vec<u8>,
+    // position of the next byte to scan
+    pos: usize,
+    // start of the object currently being read
+    start: Option<usize>,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+}
+
+impl ArraySplitter {
+    /// Feeds a chunk of the response and returns any objects it completed.
+    fn push(&mut self, chunk: &[u8]) -> Vec<CrtshResult> {
+        self.buf.extend_from_slice(chunk);
+        let mut items = Vec::new();
+
+        while self.pos < self.buf.len() {
+            let b = self.buf[self.pos];
+            if self.in_string {
+                if self.escaped {
+                    self.escaped = false;
+                } else if b == b'\\' {
+                    self.escaped = true;
+                } else if b == b'"' {
+                    self.in_string = false;
+                }
+            } else {
+                match b {
+                    b'"' => self.in_string = true,
+                    b'{' => {
+                        if self.depth == 0 {
+                            self.start = Some(self.pos);
+                        }
+                        self.depth += 1;
+                    }
+                    b'}' if self.depth > 0 => {
+                        self.depth -= 1;
+                        if self.depth == 0 {
+                            if let Some(start) = self.start.take() {
+                                match serde_json::from_slice(&self.buf[start..=self.pos]) {
+                                    Ok(item) => items.push(item),
+                                    Err(e) => debug!("skipping malformed crt.sh entry: {}", e),
+                                }
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            self.pos += 1;
+        }
+
+        // Anything before the current object has either been parsed or is array punctuation.
+        let consumed = self.start.unwrap_or(self.pos);
+        self.buf.drain(..consumed);
+        self.pos -= consumed;
+        if let Some(start) = self.start.as_mut() {
+            *start -= consumed;
+        }
+
+        items
     }
 }
 
-#[derive(Default, Clone)]
+#[derive(Clone)]
 pub struct Crtsh {
     client: Client,
+    config: Config,
+    // Shared between every host so we don't open a database connection per host.
+    pg: Arc<Mutex<Option<Arc<tokio_postgres::Client>>>>,
+}
+
+impl Default for Crtsh {
+    fn default() -> Self {
+        Self {
+            client: Client::default(),
+            config: Config::default(),
+            pg: Arc::new(Mutex::new(None)),
+        }
+    }
 }
 
 impl Crtsh {
     pub fn new(client: Client) -> Self {
-        Self { client }
+        Self {
+            client,
+            config: Config::from_env(),
+            pg: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Query a postgres database with the crt.sh schema instead of the crt.sh website.
+    ///
+    /// `conn` is a postgres connection string such as
+    /// `host=crt.sh port=5432 user=guest dbname=certwatch`.
+    pub fn postgres(mut self, conn: &str) -> Self {
+        self.config.mode = Mode::Postgres(conn.into());
+        self
+    }
+
+    /// Only return results from certificates which haven't expired yet
+    pub fn exclude_expired(mut self, exclude: bool) -> Self {
+        self.config.exclude_expired = exclude;
+        self
+    }
+
+    /// Skip entries for certificate ids which have already been seen
+    pub fn dedup(mut self, dedup: bool) -> Self {
+        self.config.dedup = dedup;
+        self
     }
 
     fn build_url(&self, host: &str) -> String {
-        format!("https://crt.sh/?q=%.{}&output=json", host)
+        if self.config.exclude_expired {
+            format!("https://crt.sh/?q=%.{}&output=json&exclude=expired", host)
+        } else {
+            format!("https://crt.sh/?q=%.{}&output=json", host)
+        }
+    }
+
+    fn build_query(&self) -> String {
+        if self.config.exclude_expired {
+            format!("{}{}", IDENTITY_QUERY, NOT_EXPIRED)
+        } else {
+            IDENTITY_QUERY.to_string()
+        }
+    }
+
+    // Returns the open database connection, or connects if there isn't one yet or it was closed.
+    async fn pg_client(&self, conn: &str) -> Result<Arc<tokio_postgres::Client>> {
+        let mut pg = self.pg.lock().await;
+        if let Some(client) = pg.as_ref() {
+            if !client.is_closed() {
+                return Ok(Arc::clone(client));
+            }
+        }
+
+        debug!("connecting to crt.sh postgres");
+        let (client, connection) = tokio_postgres::connect(conn, NoTls).await?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                warn!("crt.sh postgres connection error: {}", e);
+            }
+        });
+
+        let client = Arc::new(client);
+        *pg = Some(Arc::clone(&client));
+        Ok(client)
+    }
+
+    async fn run_http(&self, host: Arc<String>, mut tx: Sender<Vec<Subdomain>>) -> Result<usize> {
+        let uri = self.build_url(&host);
+        let mut resp = self.client.get(&uri).send().await?;
+        let mut splitter = ArraySplitter::default();
+        let mut seen = HashSet::new();
+        let mut results = Vec::with_capacity(QUEUE_SIZE);
+        let mut total = 0;
+
+        while let Some(chunk) = resp.chunk().await? {
+            for cert in splitter.push(&chunk) {
+                if self.config.dedup && is_duplicate(&mut seen, &cert) {
+                    continue;
+                }
+                results.extend(cert.subdomains());
+            }
+
+            if results.len() >= QUEUE_SIZE {
+                debug!("crt.sh queue is full, sending across channel");
+                total += results.len();
+                let _ = tx.send(results.drain(..).collect()).await;
+            }
+        }
+
+        if !results.is_empty() {
+            total += results.len();
+            let _ = tx.send(results).await;
+        }
+
+        Ok(total)
+    }
+
+    async fn run_postgres(
+        &self,
+        conn: &str,
+        host: Arc<String>,
+        mut tx: Sender<Vec<Subdomain>>,
+    ) -> Result<usize> {
+        let client = self.pg_client(conn).await?;
+        let rows = client
+            .query(self.build_query().as_str(), &[&host.as_str()])
+            .await?;
+
+        let results = rows
+            .iter()
+            .map(|row| row.try_get::<_, String>(0).map(Subdomain::from))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let total = results.len();
+        for chunk in results.chunks(QUEUE_SIZE) {
+            let _ = tx.send(chunk.to_vec()).await;
+        }
+
+        Ok(total)
     }
 }
 
 #[async_trait]
 impl DataSource for Crtsh {
-    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<String>>) -> Result<()> {
+    async fn run(&self, host: Arc<String>, tx: Sender<Vec<Subdomain>>) -> Result<()> {
         trace!("fetching data from crt.sh for: {}", &host);
-        let uri = self.build_url(&host);
-        let resp: Option<Vec<CrtshResult>> = self.client.get(&uri).send().await?.json().await?;
-
-        if let Some(data) = resp {
-            let subdomains = data.subdomains();
-            if !subdomains.is_empty() {
-                info!("Discovered {} results for: {}", subdomains.len(), &host);
-                let _ = tx.send(subdomains).await;
-                return Ok(());
-            }
+        let total = match &self.config.mode {
+            Mode::Http => self.run_http(host.clone(), tx).await?,
+            Mode::Postgres(conn) => self.run_postgres(conn, host.clone(), tx).await?,
+        };
+
+        if total > 0 {
+            info!("Discovered {} results for: {}", total, &host);
+            return Ok(());
         }
 
         warn!("no results for {} from Crt.sh", &host);
@@ -66,6 +346,81 @@ mod tests {
         assert_eq!(correct_uri, Crtsh::default().build_url("hackerone.com"));
     }
 
+    #[test]
+    fn url_builder_exclude_expired() {
+        let correct_uri = "https://crt.sh/?q=%.hackerone.com&output=json&exclude=expired";
+        assert_eq!(
+            correct_uri,
+            Crtsh::default()
+                .exclude_expired(true)
+                .build_url("hackerone.com")
+        );
+    }
+
+    #[test]
+    fn postgres_mode() {
+        let crtsh = Crtsh::default().postgres("host=localhost user=guest dbname=certwatch");
+        assert_eq!(
+            crtsh.config.mode,
+            Mode::Postgres("host=localhost user=guest dbname=certwatch".into())
+        );
+        assert_eq!(crtsh.build_query(), IDENTITY_QUERY);
+        assert!(crtsh
+            .exclude_expired(true)
+            .build_query()
+            .ends_with(NOT_EXPIRED));
+    }
+
+    #[test]
+    fn splits_name_value() {
+        let cert = CrtshResult {
+            id: Some(1),
+            common_name: "hackerone.com".into(),
+            name_value: "api.hackerone.com\nhackerone.com\n\nwww.hackerone.com".into(),
+        };
+        let hosts: Vec<String> = cert.subdomains().into_iter().map(|s| s.host).collect();
+        assert_eq!(
+            hosts,
+            vec!["api.hackerone.com", "hackerone.com", "www.hackerone.com"]
+        );
+    }
+
+    // Objects, strings containing braces and escapes split across arbitrary chunk boundaries.
+    #[test]
+    fn splits_streamed_array() {
+        let body = br#"[{"id":1,"common_name":"a.hackerone.com","name_value":"a.hackerone.com\nb.hackerone.com"},
+            {"id":2,"common_name":"{\"}","name_value":"c.hackerone.com","extra":{"x":1}}]"#;
+        let mut splitter = ArraySplitter::default();
+        let mut items = Vec::new();
+        for chunk in body.chunks(7) {
+            items.extend(splitter.push(chunk));
+        }
+
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].id, Some(1));
+        assert_eq!(items[1].common_name, "{\"}");
+        assert_eq!(items[1].name_value, "c.hackerone.com");
+    }
+
+    #[test]
+    fn dedups_by_id() {
+        let items: Vec<CrtshResult> = serde_json::from_str(
+            r#"[{"id":1,"name_value":"a.hackerone.com"},{"id":1,"name_value":"a.hackerone.com"},
+            {"name_value":"b.hackerone.com"},{"name_value":"c.hackerone.com"}]"#,
+        )
+        .unwrap();
+        let mut seen = HashSet::new();
+        let kept: Vec<&str> = items
+            .iter()
+            .filter(|cert| !is_duplicate(&mut seen, cert))
+            .map(|cert| cert.name_value.as_str())
+            .collect();
+        assert_eq!(
+            kept,
+            vec!["a.hackerone.com", "b.hackerone.com", "c.hackerone.com"]
+        );
+    }
+
     #[ignore]
     #[tokio::test]
     async fn returns_results() {
@@ -79,6 +434,23 @@ mod tests {
         assert!(!results.is_empty());
     }
 
+    // Needs `CRTSH_POSTGRES_URL` pointing at a database with the crt.sh schema, such as a
+    // local stand-in seeded with hackerone.com certificates.
+    #[ignore]
+    #[tokio::test]
+    async fn returns_postgres_results() {
+        dotenv().ok();
+        let conn = env::var("CRTSH_POSTGRES_URL").unwrap_or_else(|_| CRTSH_POSTGRES.into());
+        let (tx, mut rx) = channel(1);
+        let host = Arc::new("hackerone.com".to_owned());
+        let _ = Crtsh::default().postgres(&conn).run(host, tx).await;
+        let mut results = Vec::new();
+        for r in rx.recv().await {
+            results.extend(r)
+        }
+        assert!(!results.is_empty());
+    }
+
     #[ignore] // tests passing locally but failing on linux ci?
     #[tokio::test]
     async fn handle_no_results() {
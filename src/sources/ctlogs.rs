@@ -0,0 +1,429 @@
+use crate::error::{Result, VitaError};
+use crate::{DataSource, Subdomain};
+use async_trait::async_trait;
+use dotenv::dotenv;
+use reqwest::Client;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::mpsc::Sender;
+use tokio::sync::Mutex;
+use tracing::{debug, info, trace, warn};
+use x509_parser::certificate::{TbsCertificate, X509Certificate};
+use x509_parser::extensions::GeneralName;
+use x509_parser::traits::FromDer;
+
+// The most entries read from each log in a single run
+const DEFAULT_MAX_ENTRIES: u64 = 10_000;
+// The number of entries asked for in each get-entries request, logs may return fewer
+const DEFAULT_BATCH_SIZE: u64 = 256;
+
+// RFC 6962 `LogEntryType`s
+const X509_ENTRY: u16 = 0;
+const PRECERT_ENTRY: u16 = 1;
+// A `MerkleTreeLeaf` starts with the version, leaf type and timestamp before the entry type
+const ENTRY_TYPE_OFFSET: usize = 10;
+// A precert entry starts with the SHA-256 hash of the issuer's public key
+const ISSUER_KEY_HASH_LEN: usize = 32;
+
+#[derive(Deserialize, Debug)]
+struct SignedTreeHead {
+    tree_size: u64,
+}
+
+#[derive(Deserialize, Debug)]
+struct Entry {
+    leaf_input: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Entries {
+    entries: Vec<Entry>,
+}
+
+impl Entries {
+    // Returns the names from every certificate and precertificate in the batch.
+    fn names(&self) -> Vec<String> {
+        self.entries
+            .iter()
+            .filter_map(|e| base64::decode(&e.leaf_input).ok())
+            .filter_map(|leaf| leaf_names(&leaf))
+            .flatten()
+            .collect()
+    }
+}
+
+// Splits off a TLS `opaque<1..2^24-1>`, returning its contents and whatever comes after it.
+fn read_u24(bytes: &[u8]) -> Option<(&[u8], &[u8])> {
+    if bytes.len() < 3 {
+        return None;
+    }
+
+    let len = (usize::from(bytes[0]) << 16) | (usize::from(bytes[1]) << 8) | usize::from(bytes[2]);
+    let rest = &bytes[3..];
+    if rest.len() < len {
+        return None;
+    }
+
+    Some(rest.split_at(len))
+}
+
+// The common names and dns subject alternative names in the certificate.
+fn tbs_names(tbs: &TbsCertificate) -> Vec<String> {
+    let mut names: Vec<String> = tbs
+        .subject()
+        .iter_common_name()
+        .filter_map(|cn| cn.as_str().ok())
+        .map(str::to_lowercase)
+        .collect();
+
+    if let Ok(Some(san)) = tbs.subject_alternative_name() {
+        for name in san.value.general_names.iter() {
+            if let GeneralName::DNSName(dns) = name {
+                names.push(dns.to_lowercase());
+            }
+        }
+    }
+
+    names
+}
+
+// Parses a `MerkleTreeLeaf` from get-entries, returning the names in the certificate or
+// precertificate it holds.
+fn leaf_names(leaf: &[u8]) -> Option<Vec<String>> {
+    let entry = leaf.get(ENTRY_TYPE_OFFSET..)?;
+    if entry.len() < 2 {
+        return None;
+    }
+
+    let entry_type = u16::from_be_bytes([entry[0], entry[1]]);
+    let entry = &entry[2..];
+    match entry_type {
+        X509_ENTRY => {
+            let (der, _) = read_u24(entry)?;
+            let (_, cert) = X509Certificate::from_der(der).ok()?;
+            Some(tbs_names(&cert.tbs_certificate))
+        }
+        PRECERT_ENTRY => {
+            let (der, _) = read_u24(entry.get(ISSUER_KEY_HASH_LEN..)?)?;
+            let (_, tbs) = TbsCertificate::from_der(der).ok()?;
+            Some(tbs_names(&tbs))
+        }
+        _ => None,
+    }
+}
+
+// Loads the index to resume from for each log, a missing file means we haven't run before.
+fn load_state(path: &Path) -> Result<HashMap<String, u64>> {
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+fn save_state(path: &Path, state: &HashMap<String, u64>) -> Result<()> {
+    fs::write(path, serde_json::to_string_pretty(state)?)?;
+    Ok(())
+}
+
+#[derive(Clone)]
+pub struct CtLogs {
+    client: Client,
+    // The base urls of the logs to read, `CT_LOGS` in the environment or a `.env` file.
+    logs: Vec<String>,
+    // Where to keep the index we got up to in each log, `CT_STATE_FILE`.
+    state_file: Option<PathBuf>,
+    // `CT_MAX_ENTRIES`
+    max_entries: u64,
+    // `CT_BATCH_SIZE`
+    batch_size: u64,
+    // The logs are read once per run and the names are shared between every host.
+    names: Arc<Mutex<Option<Arc<Vec<String>>>>>,
+}
+
+impl Default for CtLogs {
+    fn default() -> Self {
+        Self {
+            client: Client::default(),
+            logs: Vec::new(),
+            state_file: None,
+            max_entries: DEFAULT_MAX_ENTRIES,
+            batch_size: DEFAULT_BATCH_SIZE,
+            names: Arc::new(Mutex::new(None)),
+        }
+    }
+}
+
+impl CtLogs {
+    pub fn new(client: Client) -> Self {
+        dotenv().ok();
+        let logs = env::var("CT_LOGS")
+            .map(|l| {
+                l.split(',')
+                    .map(str::trim)
+                    .filter(|l| !l.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let max_entries = env::var("CT_MAX_ENTRIES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MAX_ENTRIES);
+        let batch_size = env::var("CT_BATCH_SIZE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_BATCH_SIZE);
+
+        Self {
+            client,
+            logs,
+            state_file: env::var("CT_STATE_FILE").ok().map(PathBuf::from),
+            max_entries,
+            batch_size,
+            names: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Sets the base urls of the logs to read, for example `https://ct.googleapis.com/logs/argon2021/`.
+    pub fn logs<I: IntoIterator<Item = String>>(mut self, logs: I) -> Self {
+        self.logs = logs.into_iter().collect();
+        self
+    }
+
+    /// Keeps the index we got up to in each log in `path`, so the next run carries on from there
+    /// instead of reading the newest entries.
+    pub fn state_file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.state_file = Some(path.into());
+        self
+    }
+
+    /// Sets the most entries to read from each log in a single run.
+    pub fn max_entries(mut self, max: u64) -> Self {
+        self.max_entries = max;
+        self
+    }
+
+    /// Whether any logs have been configured.
+    pub fn has_logs(&self) -> bool {
+        !self.logs.is_empty()
+    }
+
+    fn sth_url(&self, log: &str) -> String {
+        format!("{}/ct/v1/get-sth", log.trim_end_matches('/'))
+    }
+
+    fn entries_url(&self, log: &str, start: u64, end: u64) -> String {
+        format!(
+            "{}/ct/v1/get-entries?start={}&end={}",
+            log.trim_end_matches('/'),
+            start,
+            end
+        )
+    }
+
+    // The entries to read as `start..end`, carrying on from where we got to last time. Without
+    // a previous index, or if the log is smaller than it, we read the newest entries.
+    fn range(&self, resume: Option<u64>, tree_size: u64) -> (u64, u64) {
+        let start = match resume {
+            Some(i) if i <= tree_size => i,
+            _ => tree_size.saturating_sub(self.max_entries),
+        };
+
+        (start, std::cmp::min(tree_size, start + self.max_entries))
+    }
+
+    async fn get<T: DeserializeOwned>(&self, uri: &str) -> Result<T> {
+        let resp = self.client.get(uri).send().await?;
+        if !resp.status().is_success() {
+            warn!("got status: {} from ct log {}", resp.status().as_str(), uri);
+            return Err(VitaError::SourceError("CtLogs".into()));
+        }
+
+        Ok(resp.json().await?)
+    }
+
+    // Reads the names from a single log, returning them along with the index to resume from.
+    async fn scan_log(&self, log: &str, resume: Option<u64>) -> Result<(Vec<String>, u64)> {
+        let sth: SignedTreeHead = self.get(&self.sth_url(log)).await?;
+        let (mut next, end) = self.range(resume, sth.tree_size);
+        debug!("reading entries {} to {} from {}", next, end, log);
+        let mut names = Vec::new();
+
+        while next < end {
+            let last = std::cmp::min(next + self.batch_size.max(1), end) - 1;
+            let entries: Entries = self.get(&self.entries_url(log, next, last)).await?;
+            if entries.entries.is_empty() {
+                break;
+            }
+
+            next += entries.entries.len() as u64;
+            names.extend(entries.names());
+        }
+
+        Ok((names, next))
+    }
+
+    // Returns the names from every log, reading them the first time they're needed.
+    async fn names(&self) -> Result<Arc<Vec<String>>> {
+        let mut cached = self.names.lock().await;
+        if let Some(names) = cached.as_ref() {
+            return Ok(Arc::clone(names));
+        }
+
+        let mut state = match &self.state_file {
+            Some(path) => load_state(path)?,
+            None => HashMap::new(),
+        };
+
+        let mut names = Vec::new();
+        for log in self.logs.iter() {
+            match self.scan_log(log, state.get(log).copied()).await {
+                Ok((found, next)) => {
+                    info!("read {} names from {}", found.len(), log);
+                    names.extend(found);
+                    state.insert(log.to_string(), next);
+                }
+                Err(e) => warn!("couldn't read ct log {}: {}", log, e),
+            }
+        }
+
+        if let Some(path) = &self.state_file {
+            save_state(path, &state)?;
+        }
+
+        names.sort();
+        names.dedup();
+        let names = Arc::new(names);
+        *cached = Some(Arc::clone(&names));
+        Ok(names)
+    }
+}
+
+#[async_trait]
+impl DataSource for CtLogs {
+    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<Subdomain>>) -> Result<()> {
+        trace!("fetching data from ct logs for: {}", &host);
+        let names = self.names().await?;
+        let suffix = format!(".{}", host);
+        let subdomains: Vec<Subdomain> = names
+            .iter()
+            .filter(|n| n.as_str() == host.as_str() || n.ends_with(&suffix))
+            .map(Subdomain::from)
+            .collect();
+
+        if !subdomains.is_empty() {
+            info!("Discovered {} results for: {}", subdomains.len(), &host);
+            let _ = tx.send(subdomains).await;
+            return Ok(());
+        }
+
+        warn!("no results for {} from CtLogs", &host);
+        Err(VitaError::SourceError("CtLogs".into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use matches::matches;
+    use tokio::sync::mpsc::channel;
+
+    fn entries() -> Entries {
+        serde_json::from_str(include_str!("../../tests/fixtures/ctlogs/get-entries.json")).unwrap()
+    }
+
+    #[test]
+    fn url_builder() {
+        let logs = CtLogs::default();
+        assert_eq!(
+            logs.sth_url("https://ct.googleapis.com/logs/argon2021/"),
+            "https://ct.googleapis.com/logs/argon2021/ct/v1/get-sth"
+        );
+        assert_eq!(
+            logs.entries_url("http://localhost:6962", 0, 255),
+            "http://localhost:6962/ct/v1/get-entries?start=0&end=255"
+        );
+    }
+
+    // The fixture has the same certificate logged as a certificate and a precertificate.
+    #[test]
+    fn parses_leaves() {
+        let names: Vec<Option<Vec<String>>> = entries()
+            .entries
+            .iter()
+            .map(|e| leaf_names(&base64::decode(&e.leaf_input).unwrap()))
+            .collect();
+        let expected = vec![
+            "hackerone.com".to_string(),
+            "hackerone.com".to_string(),
+            "*.hackerone.com".to_string(),
+            "api.hackerone.com".to_string(),
+        ];
+        assert_eq!(names, vec![Some(expected.clone()), Some(expected)]);
+    }
+
+    #[test]
+    fn handles_truncated_leaves() {
+        assert_eq!(leaf_names(&[0, 0, 1]), None);
+        assert_eq!(leaf_names(&[0; 16]), None);
+    }
+
+    #[test]
+    fn range() {
+        let logs = CtLogs::default().max_entries(100);
+        assert_eq!(logs.range(None, 1000), (900, 1000));
+        assert_eq!(logs.range(None, 50), (0, 50));
+        assert_eq!(logs.range(Some(500), 1000), (500, 600));
+        // The log is smaller than our index so it's not the log we read before
+        assert_eq!(logs.range(Some(5000), 1000), (900, 1000));
+    }
+
+    #[test]
+    fn state_round_trip() {
+        let path = env::temp_dir().join("vita-ctlogs-state.json");
+        let _ = fs::remove_file(&path);
+        assert!(load_state(&path).unwrap().is_empty());
+
+        let mut state = HashMap::new();
+        state.insert("http://localhost:6962".to_string(), 42);
+        save_state(&path, &state).unwrap();
+        assert_eq!(load_state(&path).unwrap(), state);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn filters_names() {
+        let (tx, mut rx) = channel(1);
+        let logs = CtLogs::default();
+        let mut names = entries().names();
+        names.sort();
+        names.dedup();
+        *logs.names.lock().await = Some(Arc::new(names));
+
+        let host = Arc::new("api.hackerone.com".to_string());
+        assert!(logs.run(host, tx).await.is_ok());
+        assert_eq!(
+            rx.recv().await.unwrap(),
+            vec![Subdomain::from("api.hackerone.com")]
+        );
+    }
+
+    #[tokio::test]
+    async fn handle_no_results() {
+        let (tx, _rx) = channel(1);
+        let logs = CtLogs::default();
+        *logs.names.lock().await = Some(Arc::new(entries().names()));
+
+        let host = Arc::new("anVubmxpa2VzdGVh.com".to_string());
+        assert!(matches!(
+            logs.run(host, tx).await.err().unwrap(),
+            VitaError::SourceError(_)
+        ));
+    }
+}
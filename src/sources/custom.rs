@@ -0,0 +1,366 @@
+use crate::error::{Result, VitaError};
+use crate::{DataSource, Subdomain};
+use async_trait::async_trait;
+use dotenv::dotenv;
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::mpsc::Sender;
+use tracing::{debug, info, trace, warn};
+use url::Url;
+
+const DEFAULT_MAX_PAGES: usize = 10;
+
+fn default_max_pages() -> usize {
+    DEFAULT_MAX_PAGES
+}
+
+fn default_first_page() -> usize {
+    1
+}
+
+fn default_pagination() -> Pagination {
+    Pagination::None
+}
+
+/// How an api splits its results across pages.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Pagination {
+    /// Everything is returned in a single response.
+    None,
+    /// The url of the next page is at the json `pointer` in each response. Relative urls are
+    /// resolved against the current page.
+    Next {
+        pointer: String,
+        #[serde(default = "default_max_pages")]
+        max: usize,
+    },
+    /// The page is selected with the `param` query parameter, counting up from `first`.
+    Page {
+        param: String,
+        #[serde(default = "default_first_page")]
+        first: usize,
+        #[serde(default = "default_max_pages")]
+        max: usize,
+    },
+}
+
+/// A header sent with every request, with its value read from the environment.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Auth {
+    pub header: String,
+    /// The environment variable, or `.env` entry, holding the value.
+    pub env: String,
+    /// Put in front of the value, for example `Bearer `.
+    #[serde(default)]
+    pub prefix: String,
+}
+
+/// Describes an api which returns subdomains as json.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct JsonSourceDefinition {
+    /// The name used in logs, errors and with `--exclude`.
+    pub name: String,
+    /// The url to query, `{domain}` is replaced with the host being searched.
+    pub url: String,
+    #[serde(default)]
+    pub auth: Option<Auth>,
+    /// A json pointer to the hostnames in the response. It can point at a single string, or an
+    /// array of strings or objects.
+    pub hosts: String,
+    /// A json pointer to the hostname within each object in the `hosts` array.
+    #[serde(default)]
+    pub field: Option<String>,
+    #[serde(default = "default_pagination")]
+    pub pagination: Pagination,
+}
+
+impl JsonSourceDefinition {
+    fn build_url(&self, host: &str) -> String {
+        self.url.replace("{domain}", host)
+    }
+
+    fn max_pages(&self) -> usize {
+        match self.pagination {
+            Pagination::None => 1,
+            Pagination::Next { max, .. } | Pagination::Page { max, .. } => max,
+        }
+    }
+
+    // The url of the `n`th page when paginating by page, counting from 0.
+    fn page_url(&self, uri: &str, n: usize) -> Result<String> {
+        match &self.pagination {
+            Pagination::Page { param, first, .. } => {
+                let mut url = Url::parse(uri).map_err(|e| {
+                    warn!("invalid url for {}: {}", &self.name, e);
+                    VitaError::SourceError(self.name.clone())
+                })?;
+                url.query_pairs_mut()
+                    .append_pair(param, &(first + n).to_string());
+                Ok(url.to_string())
+            }
+            _ => Ok(uri.to_string()),
+        }
+    }
+
+    fn extract_hosts(&self, value: &Value) -> Vec<String> {
+        let hosts = match value.pointer(&self.hosts) {
+            Some(Value::Array(items)) => items
+                .iter()
+                .filter_map(|item| match &self.field {
+                    Some(field) => item.pointer(field).and_then(Value::as_str),
+                    None => item.as_str(),
+                })
+                .collect(),
+            Some(Value::String(host)) => vec![host.as_str()],
+            _ => Vec::new(),
+        };
+
+        hosts
+            .into_iter()
+            .map(|h| h.trim().trim_end_matches('.').to_lowercase())
+            .filter(|h| !h.is_empty())
+            .collect()
+    }
+
+    fn next_url(&self, uri: &str, value: &Value) -> Option<String> {
+        match &self.pagination {
+            Pagination::Next { pointer, .. } => value
+                .pointer(pointer)
+                .and_then(Value::as_str)
+                .filter(|next| !next.is_empty())
+                .and_then(|next| Url::parse(uri).ok()?.join(next).ok())
+                .map(|u| u.to_string()),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct ConfigFile {
+    #[serde(default, rename = "source")]
+    sources: Vec<JsonSourceDefinition>,
+}
+
+/// Reads the json source definitions from a toml config file, each declared in a `[[source]]`
+/// table.
+pub fn load(path: impl AsRef<Path>) -> Result<Vec<JsonSourceDefinition>> {
+    let contents = fs::read_to_string(path)?;
+    let config: ConfigFile = toml::from_str(&contents)?;
+    Ok(config.sources)
+}
+
+/// `JsonSource` is a `DataSource` for user defined apis which return subdomains as json.
+#[derive(Clone)]
+pub struct JsonSource {
+    client: Client,
+    definition: Arc<JsonSourceDefinition>,
+}
+
+impl JsonSource {
+    pub fn new(client: Client, definition: JsonSourceDefinition) -> Self {
+        Self {
+            client,
+            definition: Arc::new(definition),
+        }
+    }
+
+    // The header to authenticate with, if the source needs one.
+    fn auth(&self) -> Result<Option<(String, String)>> {
+        let auth = match &self.definition.auth {
+            Some(auth) => auth,
+            None => return Ok(None),
+        };
+
+        dotenv().ok();
+        match env::var(&auth.env) {
+            Ok(value) => Ok(Some((
+                auth.header.clone(),
+                format!("{}{}", auth.prefix, value),
+            ))),
+            Err(_) => Err(VitaError::UnsetKeys(vec![auth.env.clone()])),
+        }
+    }
+
+    async fn fetch(&self, uri: &str, auth: &Option<(String, String)>) -> Result<Value> {
+        let mut req = self.client.get(uri);
+        if let Some((header, value)) = auth {
+            req = req.header(header.as_str(), value.as_str());
+        }
+
+        let resp = req.send().await?;
+        let name = &self.definition.name;
+        match resp.status() {
+            StatusCode::TOO_MANY_REQUESTS => Err(VitaError::QuotaExceeded(name.clone())),
+            s @ StatusCode::UNAUTHORIZED | s @ StatusCode::FORBIDDEN => {
+                warn!("got status: {} from {}", s.as_str(), name);
+                Err(VitaError::AuthError(name.clone()))
+            }
+            s if !s.is_success() => {
+                warn!("got status: {} from {}", s.as_str(), name);
+                Err(VitaError::SourceError(name.clone()))
+            }
+            _ => Ok(resp.json().await?),
+        }
+    }
+}
+
+#[async_trait]
+impl DataSource for JsonSource {
+    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<Subdomain>>) -> Result<()> {
+        let def = &self.definition;
+        trace!("fetching data from {} for: {}", &def.name, &host);
+        let auth = self.auth()?;
+        let base = def.build_url(&host);
+        let mut uri = def.page_url(&base, 0)?;
+        let mut seen = HashSet::new();
+
+        for n in 0..def.max_pages() {
+            let value = match self.fetch(&uri, &auth).await {
+                Ok(value) => value,
+                Err(e) if !seen.is_empty() => {
+                    debug!("stopped paging {} at page {}: {}", &def.name, n + 1, e);
+                    break;
+                }
+                Err(e) => return Err(e),
+            };
+
+            let subdomains: Vec<Subdomain> = def
+                .extract_hosts(&value)
+                .into_iter()
+                .filter(|h| seen.insert(h.clone()))
+                .map(Subdomain::from)
+                .collect();
+
+            if subdomains.is_empty() {
+                break;
+            }
+            let _ = tx.send(subdomains).await;
+
+            uri = match &def.pagination {
+                Pagination::Page { .. } => def.page_url(&base, n + 1)?,
+                Pagination::Next { .. } => match def.next_url(&uri, &value) {
+                    Some(next) => next,
+                    None => break,
+                },
+                Pagination::None => break,
+            };
+        }
+
+        if !seen.is_empty() {
+            info!("Discovered {} results for: {}", seen.len(), &host);
+            return Ok(());
+        }
+
+        warn!("no results for {} from {}", &host, &def.name);
+        Err(VitaError::SourceError(def.name.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn definitions() -> Vec<JsonSourceDefinition> {
+        toml::from_str::<ConfigFile>(
+            r#"
+            [[source]]
+            name = "inventory"
+            url = "https://inventory.internal/api/v1/domains/{domain}/subdomains"
+            hosts = "/data"
+            field = "/attributes/hostname"
+            auth = { header = "Authorization", env = "INVENTORY_TOKEN", prefix = "Bearer " }
+            pagination = { type = "next", pointer = "/links/next" }
+
+            [[source]]
+            name = "dns-history"
+            url = "https://dns.internal/search?q={domain}"
+            hosts = "/results"
+            pagination = { type = "page", param = "p", max = 3 }
+            "#,
+        )
+        .unwrap()
+        .sources
+    }
+
+    #[test]
+    fn parses_config() {
+        let defs = definitions();
+        assert_eq!(defs.len(), 2);
+        assert_eq!(
+            defs[0].auth,
+            Some(Auth {
+                header: "Authorization".into(),
+                env: "INVENTORY_TOKEN".into(),
+                prefix: "Bearer ".into(),
+            })
+        );
+        assert_eq!(
+            defs[0].pagination,
+            Pagination::Next {
+                pointer: "/links/next".into(),
+                max: DEFAULT_MAX_PAGES
+            }
+        );
+        assert_eq!(
+            defs[1].pagination,
+            Pagination::Page {
+                param: "p".into(),
+                first: 1,
+                max: 3
+            }
+        );
+    }
+
+    #[test]
+    fn url_builder() {
+        let def = &definitions()[1];
+        let base = def.build_url("hackerone.com");
+        assert_eq!(base, "https://dns.internal/search?q=hackerone.com");
+        assert_eq!(
+            def.page_url(&base, 1).unwrap(),
+            "https://dns.internal/search?q=hackerone.com&p=2"
+        );
+    }
+
+    #[test]
+    fn extracts_hosts_and_next_link() {
+        let def = &definitions()[0];
+        let uri = def.build_url("hackerone.com");
+        let value = json!({
+            "data": [
+                {"id": 1, "attributes": {"hostname": "API.hackerone.com."}},
+                {"id": 2, "attributes": {"hostname": "docs.hackerone.com"}},
+                {"id": 3, "attributes": {}}
+            ],
+            "links": {"next": "?cursor=abc"}
+        });
+
+        assert_eq!(
+            def.extract_hosts(&value),
+            vec!["api.hackerone.com", "docs.hackerone.com"]
+        );
+        assert_eq!(
+            def.next_url(&uri, &value).unwrap(),
+            "https://inventory.internal/api/v1/domains/hackerone.com/subdomains?cursor=abc"
+        );
+        assert_eq!(def.next_url(&uri, &json!({"links": {"next": null}})), None);
+    }
+
+    #[test]
+    fn extracts_plain_strings() {
+        let def = &definitions()[1];
+        let value = json!({"results": ["www.hackerone.com", 42, "hackerone.com"]});
+        assert_eq!(
+            def.extract_hosts(&value),
+            vec!["www.hackerone.com", "hackerone.com"]
+        );
+    }
+}
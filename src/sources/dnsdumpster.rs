@@ -0,0 +1,201 @@
+use crate::error::{Result, VitaError};
+use crate::{DataSource, Subdomain};
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, COOKIE, REFERER, SET_COOKIE};
+use reqwest::Client;
+use scraper::{Html, Selector};
+use std::collections::HashSet;
+use std::sync::Arc;
+use tokio::sync::mpsc::Sender;
+use tracing::{debug, info, trace, warn};
+
+const URL: &str = "https://dnsdumpster.com/";
+
+// Pulls the value of the `csrftoken` cookie out of the `Set-Cookie` headers.
+fn csrf_cookie(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .filter_map(|v| v.split(';').next())
+        .find_map(|v| v.trim().strip_prefix("csrftoken="))
+        .map(str::to_string)
+}
+
+// The token the search form has to be posted with.
+fn csrf_form_token(body: &str) -> Option<String> {
+    let html = Html::parse_document(body);
+    let selector = Selector::parse(r#"input[name="csrfmiddlewaretoken"]"#).unwrap();
+    html.select(&selector)
+        .next()
+        .and_then(|input| input.value().attr("value"))
+        .map(str::to_string)
+}
+
+// Reads the hostnames, and the address they resolve to, from the result tables. The first cell
+// of each row holds the name, prefixed with the preference for MX records, and the second holds
+// the address. Rows without an address, like TXT records, are skipped.
+fn hosts_from_tables(body: &str) -> Vec<Subdomain> {
+    let html = Html::parse_document(body);
+    let rows = Selector::parse("div.table-responsive table tr").unwrap();
+    let cells = Selector::parse("td").unwrap();
+    let mut seen = HashSet::new();
+    let mut subdomains = Vec::new();
+
+    for row in html.select(&rows) {
+        let mut tds = row.select(&cells);
+        let (name, ip) = match (tds.next(), tds.next()) {
+            (Some(name), Some(ip)) => (name, ip),
+            _ => continue,
+        };
+
+        let host = match name
+            .text()
+            .next()
+            .and_then(|t| t.split_whitespace().last())
+            .map(|h| h.trim_end_matches('.').to_lowercase())
+        {
+            Some(h) if h.contains('.') => h,
+            _ => continue,
+        };
+
+        if seen.insert(host.clone()) {
+            subdomains.push(Subdomain::with_ip(host, ip.text().next().unwrap_or("")));
+        }
+    }
+
+    subdomains
+}
+
+#[derive(Default, Clone)]
+pub struct DnsDumpster {
+    client: Client,
+}
+
+impl DnsDumpster {
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+
+    // Loads the search page for the cookie and form token needed to post a search.
+    async fn tokens(&self) -> Result<(String, String)> {
+        let resp = self.client.get(URL).send().await?;
+        let cookie = csrf_cookie(resp.headers());
+        let form_token = csrf_form_token(&resp.text().await?);
+
+        match (cookie, form_token) {
+            (Some(cookie), Some(form_token)) => Ok((cookie, form_token)),
+            _ => {
+                warn!("couldn't find the csrf tokens on dnsdumpster");
+                Err(VitaError::SourceError("DnsDumpster".into()))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl DataSource for DnsDumpster {
+    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<Subdomain>>) -> Result<()> {
+        trace!("fetching data from dnsdumpster for: {}", &host);
+        let (cookie, form_token) = self.tokens().await?;
+        debug!("got dnsdumpster csrf token for: {}", &host);
+
+        let params = [
+            ("csrfmiddlewaretoken", form_token.as_str()),
+            ("targetip", host.as_str()),
+            ("user", "free"),
+        ];
+        let resp = self
+            .client
+            .post(URL)
+            .header(COOKIE, format!("csrftoken={}", cookie))
+            .header(REFERER, URL)
+            .form(&params)
+            .send()
+            .await?;
+
+        if !resp.status().is_success() {
+            warn!("got status: {} from dnsdumpster", resp.status().as_str());
+            return Err(VitaError::SourceError("DnsDumpster".into()));
+        }
+
+        let subdomains = hosts_from_tables(&resp.text().await?);
+        if !subdomains.is_empty() {
+            info!("Discovered {} results for: {}", subdomains.len(), &host);
+            let _ = tx.send(subdomains).await;
+            return Ok(());
+        }
+
+        warn!("no results for {} from DnsDumpster", &host);
+        Err(VitaError::SourceError("DnsDumpster".into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use matches::matches;
+    use reqwest::header::HeaderValue;
+    use tokio::sync::mpsc::channel;
+
+    #[test]
+    fn reads_csrf_tokens() {
+        let mut headers = HeaderMap::new();
+        headers.append(
+            SET_COOKIE,
+            HeaderValue::from_static("sessionid=abc; Path=/"),
+        );
+        headers.append(
+            SET_COOKIE,
+            HeaderValue::from_static(
+                "csrftoken=Xk2p9LmQ; expires=Sat, 30 Oct 2021 10:00:00 GMT; Max-Age=31449600; Path=/; SameSite=Lax",
+            ),
+        );
+        assert_eq!(csrf_cookie(&headers).unwrap(), "Xk2p9LmQ");
+
+        let body = include_str!("../../tests/fixtures/dnsdumpster/index.html");
+        assert_eq!(
+            csrf_form_token(body).unwrap(),
+            "bS3v1Qm9XxGmH0cTz4pL7aKqE2dRw8yNfU6iJ5oVhC1sBtM0eZ"
+        );
+    }
+
+    #[test]
+    fn parses_host_tables() {
+        let body = include_str!("../../tests/fixtures/dnsdumpster/results.html");
+        assert_eq!(
+            hosts_from_tables(body),
+            vec![
+                Subdomain::with_ip("gordon.ns.cloudflare.com", "172.64.33.162"),
+                Subdomain::with_ip("aspmx.l.google.com", "142.250.27.27"),
+                Subdomain::with_ip("hackerone.com", "104.16.99.52"),
+                Subdomain::with_ip("api.hackerone.com", "104.16.100.52"),
+                Subdomain::with_ip("mta-sts.forwarding.hackerone.com", "185.199.108.153"),
+            ]
+        );
+    }
+
+    #[ignore]
+    #[tokio::test]
+    async fn returns_results() {
+        let (tx, mut rx) = channel(1);
+        let host = Arc::new("hackerone.com".to_owned());
+        let _ = DnsDumpster::default().run(host, tx).await;
+        let mut results = Vec::new();
+        for r in rx.recv().await {
+            results.extend(r)
+        }
+        assert!(!results.is_empty());
+    }
+
+    #[ignore]
+    #[tokio::test]
+    async fn handle_no_results() {
+        let (tx, _rx) = channel(1);
+        let host = Arc::new("anVubmxpa2VzdGVh.com".to_string());
+        assert!(matches!(
+            DnsDumpster::default().run(host, tx).await.err().unwrap(),
+            VitaError::SourceError(_)
+        ));
+    }
+}
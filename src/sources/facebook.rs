@@ -1,5 +1,5 @@
 use crate::error::{Result, VitaError};
-use crate::{DataSource, IntoSubdomain};
+use crate::{DataSource, IntoSubdomain, Subdomain};
 use async_trait::async_trait;
 use dotenv::dotenv;
 use reqwest::Client;
@@ -69,10 +69,10 @@ struct FacebookResult {
 }
 
 impl IntoSubdomain for FacebookResult {
-    fn subdomains(&self) -> Vec<String> {
+    fn subdomains(&self) -> Vec<Subdomain> {
         self.data
             .iter()
-            .flat_map(|s| s.domains.to_owned())
+            .flat_map(|s| s.domains.iter().map(Subdomain::from))
             .collect()
     }
 }
@@ -98,7 +98,7 @@ impl Facebook {
 
 #[async_trait]
 impl DataSource for Facebook {
-    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<String>>) -> Result<()> {
+    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<Subdomain>>) -> Result<()> {
         let access_token = match Creds::read_creds() {
             Ok(c) => c.authenticate(self.client.clone()).await?,
             Err(e) => return Err(e),
@@ -0,0 +1,437 @@
+use crate::error::{Result, VitaError};
+use crate::{find_hosts, host_regex, DataSource, Subdomain};
+use async_trait::async_trait;
+use dotenv::dotenv;
+use futures::stream::{self, StreamExt};
+use regex::Regex;
+use reqwest::header::{HeaderMap, ACCEPT, AUTHORIZATION, USER_AGENT};
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::env;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc::Sender;
+use tracing::{debug, info, trace, warn};
+
+const PER_PAGE: usize = 100;
+// Code search only returns the first 1000 results
+const DEFAULT_MAX_PAGES: usize = 10;
+// How many files are downloaded at the same time
+const FETCH_CONCURRENCY: usize = 5;
+// The longest we'll wait for a rate limited token to reset before giving up
+const MAX_WAIT: Duration = Duration::from_secs(60);
+// How many times a search is retried after hitting the rate limit before giving up
+const MAX_RETRIES: usize = 5;
+
+struct Creds {
+    tokens: Vec<String>,
+}
+
+impl Creds {
+    // `GITHUB_TOKEN` can hold several comma separated tokens to rotate through.
+    fn read_creds() -> Result<Self> {
+        dotenv().ok();
+        let tokens: Vec<String> = env::var("GITHUB_TOKEN")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .map(str::to_string)
+            .collect();
+
+        if tokens.is_empty() {
+            return Err(VitaError::UnsetKeys(vec!["GITHUB_TOKEN".into()]));
+        }
+
+        Ok(Self { tokens })
+    }
+}
+
+struct Token {
+    token: String,
+    // When the token can be used again after hitting the rate limit
+    limited_until: Option<SystemTime>,
+}
+
+// Hands out the tokens in turn, skipping any which are rate limited.
+struct TokenPool {
+    tokens: Mutex<Vec<Token>>,
+    next: AtomicUsize,
+}
+
+impl TokenPool {
+    fn new(tokens: Vec<String>) -> Self {
+        Self {
+            tokens: Mutex::new(
+                tokens
+                    .into_iter()
+                    .map(|token| Token {
+                        token,
+                        limited_until: None,
+                    })
+                    .collect(),
+            ),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.tokens.lock().unwrap().is_empty()
+    }
+
+    // Returns the index and value of the next usable token, or how long until one resets.
+    fn acquire(&self, now: SystemTime) -> std::result::Result<(usize, String), Duration> {
+        let tokens = self.tokens.lock().unwrap();
+        let start = self.next.fetch_add(1, Ordering::SeqCst);
+        let mut wait = Duration::from_secs(u64::MAX);
+
+        for i in 0..tokens.len() {
+            let index = (start + i) % tokens.len();
+            match tokens[index].limited_until {
+                Some(until) if until > now => {
+                    wait = std::cmp::min(wait, until.duration_since(now).unwrap_or_default());
+                }
+                _ => return Ok((index, tokens[index].token.clone())),
+            }
+        }
+
+        Err(wait)
+    }
+
+    fn limit(&self, index: usize, until: SystemTime) {
+        if let Some(token) = self.tokens.lock().unwrap().get_mut(index) {
+            token.limited_until = Some(until);
+        }
+    }
+}
+
+// GitHub also answers with a 403 when a token isn't allowed to search, only the ones that come
+// with the rate limit headers mean the token should be rested.
+fn is_rate_limited(status: StatusCode, headers: &HeaderMap) -> bool {
+    match status {
+        StatusCode::TOO_MANY_REQUESTS => true,
+        StatusCode::FORBIDDEN => {
+            headers.contains_key("retry-after")
+                || headers
+                    .get("x-ratelimit-remaining")
+                    .and_then(|v| v.to_str().ok())
+                    == Some("0")
+        }
+        _ => false,
+    }
+}
+
+// Works out when a rate limited token can be used again from the response headers.
+fn rate_limit_reset(headers: &HeaderMap, now: SystemTime) -> SystemTime {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok())
+    };
+
+    if let Some(secs) = header("retry-after") {
+        return now + Duration::from_secs(secs);
+    }
+
+    match header("x-ratelimit-reset") {
+        Some(epoch) => UNIX_EPOCH + Duration::from_secs(epoch),
+        None => now + MAX_WAIT,
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct CodeResult {
+    html_url: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct SearchResult {
+    items: Vec<CodeResult>,
+}
+
+// Turns the link to a file on github into a link to its raw contents.
+fn raw_url(html_url: &str) -> Option<String> {
+    let path = html_url.strip_prefix("https://github.com/")?;
+    Some(format!(
+        "https://raw.githubusercontent.com/{}",
+        path.replacen("/blob/", "/", 1)
+    ))
+}
+
+fn extract_hosts(re: &Regex, contents: &str) -> HashSet<String> {
+    find_hosts(re, contents).into_iter().collect()
+}
+
+#[derive(Clone)]
+pub struct GitHub {
+    client: Client,
+    // Shared between every host so rate limited tokens are skipped everywhere.
+    tokens: Arc<TokenPool>,
+    // `GITHUB_MAX_PAGES` in the environment or a `.env` file.
+    max_pages: usize,
+}
+
+impl Default for GitHub {
+    fn default() -> Self {
+        Self {
+            client: Client::default(),
+            tokens: Arc::new(TokenPool::new(Vec::new())),
+            max_pages: DEFAULT_MAX_PAGES,
+        }
+    }
+}
+
+impl GitHub {
+    pub fn new(client: Client) -> Self {
+        let tokens = Creds::read_creds().map(|c| c.tokens).unwrap_or_default();
+        let max_pages = env::var("GITHUB_MAX_PAGES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MAX_PAGES);
+
+        Self {
+            client,
+            tokens: Arc::new(TokenPool::new(tokens)),
+            max_pages,
+        }
+    }
+
+    fn build_url(&self, host: &str, page: usize) -> String {
+        format!(
+            "https://api.github.com/search/code?q=%22{}%22&per_page={}&page={}",
+            host, PER_PAGE, page
+        )
+    }
+
+    // Runs a search with the next available token, moving on to the others when one is rate
+    // limited.
+    async fn search(&self, uri: &str) -> Result<SearchResult> {
+        for _ in 0..=MAX_RETRIES {
+            let (index, token) = match self.tokens.acquire(SystemTime::now()) {
+                Ok(t) => t,
+                Err(wait) if wait <= MAX_WAIT => {
+                    debug!("all github tokens are rate limited, waiting {:?}", wait);
+                    tokio::time::delay_for(wait).await;
+                    continue;
+                }
+                Err(_) => return Err(VitaError::QuotaExceeded("GitHub".into())),
+            };
+
+            let resp = self
+                .client
+                .get(uri)
+                .header(AUTHORIZATION, format!("token {}", token))
+                .header(ACCEPT, "application/vnd.github.v3+json")
+                .header(USER_AGENT, "vita")
+                .send()
+                .await?;
+
+            match resp.status() {
+                s if is_rate_limited(s, resp.headers()) => {
+                    let until = rate_limit_reset(resp.headers(), SystemTime::now());
+                    debug!("github token {} is rate limited", index);
+                    self.tokens.limit(index, until);
+                }
+                s @ StatusCode::UNAUTHORIZED | s @ StatusCode::FORBIDDEN => {
+                    warn!("github token {} was rejected with: {}", index, s.as_str());
+                    return Err(VitaError::AuthError("GitHub".into()));
+                }
+                s if !s.is_success() => {
+                    warn!("got status: {} from github", s.as_str());
+                    return Err(VitaError::SourceError("GitHub".into()));
+                }
+                _ => return Ok(resp.json().await?),
+            }
+        }
+
+        warn!("github is still rate limited after {} retries", MAX_RETRIES);
+        Err(VitaError::QuotaExceeded("GitHub".into()))
+    }
+
+    async fn fetch_hosts(&self, re: &Regex, html_url: String) -> HashSet<String> {
+        let uri = match raw_url(&html_url) {
+            Some(u) => u,
+            None => return HashSet::new(),
+        };
+
+        match self.client.get(&uri).send().await {
+            Ok(resp) => match resp.text().await {
+                Ok(contents) => extract_hosts(re, &contents),
+                Err(e) => {
+                    debug!("couldn't read {}: {}", &uri, e);
+                    HashSet::new()
+                }
+            },
+            Err(e) => {
+                debug!("couldn't fetch {}: {}", &uri, e);
+                HashSet::new()
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl DataSource for GitHub {
+    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<Subdomain>>) -> Result<()> {
+        trace!("fetching data from github for: {}", &host);
+        if self.tokens.is_empty() {
+            return Err(VitaError::UnsetKeys(vec!["GITHUB_TOKEN".into()]));
+        }
+
+        let re = host_regex(&host);
+        let mut seen = HashSet::new();
+        for page in 1..=self.max_pages {
+            let results = match self.search(&self.build_url(&host, page)).await {
+                Ok(r) => r,
+                Err(e) if !seen.is_empty() => {
+                    warn!("couldn't fetch page {} from github: {}", page, e);
+                    break;
+                }
+                Err(e) => return Err(e),
+            };
+
+            let last_page = results.items.len() < PER_PAGE;
+            let mut files = stream::iter(results.items)
+                .map(|item| self.fetch_hosts(&re, item.html_url))
+                .buffer_unordered(FETCH_CONCURRENCY);
+
+            let mut subdomains = Vec::new();
+            while let Some(hosts) = files.next().await {
+                subdomains.extend(
+                    hosts
+                        .into_iter()
+                        .filter(|h| seen.insert(h.clone()))
+                        .map(Subdomain::from),
+                );
+            }
+
+            if !subdomains.is_empty() {
+                let _ = tx.send(subdomains).await;
+            }
+
+            if last_page {
+                break;
+            }
+        }
+
+        if !seen.is_empty() {
+            info!("Discovered {} results for: {}", seen.len(), &host);
+            return Ok(());
+        }
+
+        warn!("no results for {} from GitHub", &host);
+        Err(VitaError::SourceError("GitHub".into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use matches::matches;
+    use reqwest::header::HeaderValue;
+    use tokio::sync::mpsc::channel;
+
+    #[test]
+    fn url_builder() {
+        let correct_uri =
+            "https://api.github.com/search/code?q=%22hackerone.com%22&per_page=100&page=2";
+        assert_eq!(correct_uri, GitHub::default().build_url("hackerone.com", 2));
+    }
+
+    #[test]
+    fn builds_raw_url() {
+        assert_eq!(
+            raw_url("https://github.com/Hacker0x01/docs/blob/4b2d1a/config/hosts.yml").unwrap(),
+            "https://raw.githubusercontent.com/Hacker0x01/docs/4b2d1a/config/hosts.yml"
+        );
+        assert_eq!(raw_url("https://gitlab.com/a/b/blob/c/d"), None);
+    }
+
+    #[test]
+    fn extracts_hostnames() {
+        let contents = r#"
+            API_URL = "https://API.hackerone.com/v1"
+            staging: internal-staging.eu-west-1.hackerone.com:8080
+            not_ours: hackerone.com.evil.io api.hackerone.com.evil.io
+            encoded: https%3A%2F%2Fapi.hackerone.com%2Fv1
+            docs-hackerone.com
+            bare: hackerone.com
+        "#;
+        let mut hosts: Vec<String> = extract_hosts(&host_regex("hackerone.com"), contents)
+            .into_iter()
+            .collect();
+        hosts.sort();
+        assert_eq!(
+            hosts,
+            vec![
+                "api.hackerone.com",
+                "internal-staging.eu-west-1.hackerone.com"
+            ]
+        );
+    }
+
+    #[test]
+    fn detects_rate_limits() {
+        let mut headers = HeaderMap::new();
+        assert!(is_rate_limited(StatusCode::TOO_MANY_REQUESTS, &headers));
+        assert!(!is_rate_limited(StatusCode::FORBIDDEN, &headers));
+
+        headers.insert("x-ratelimit-remaining", HeaderValue::from_static("12"));
+        assert!(!is_rate_limited(StatusCode::FORBIDDEN, &headers));
+        headers.insert("x-ratelimit-remaining", HeaderValue::from_static("0"));
+        assert!(is_rate_limited(StatusCode::FORBIDDEN, &headers));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("retry-after", HeaderValue::from_static("30"));
+        assert!(is_rate_limited(StatusCode::FORBIDDEN, &headers));
+        assert!(!is_rate_limited(StatusCode::OK, &headers));
+    }
+
+    #[test]
+    fn rotates_rate_limited_tokens() {
+        let pool = TokenPool::new(vec!["a".into(), "b".into()]);
+        let now = SystemTime::now();
+        let (first, _) = pool.acquire(now).unwrap();
+        pool.limit(first, now + Duration::from_secs(30));
+
+        // Only the other token is handed out while the first is limited
+        for _ in 0..3 {
+            assert_ne!(pool.acquire(now).unwrap().0, first);
+        }
+
+        pool.limit(1 - first, now + Duration::from_secs(10));
+        assert_eq!(pool.acquire(now), Err(Duration::from_secs(10)));
+        assert!(pool.acquire(now + Duration::from_secs(31)).is_ok());
+    }
+
+    #[ignore]
+    #[tokio::test]
+    async fn returns_results() {
+        let (tx, mut rx) = channel(1);
+        let host = Arc::new("hackerone.com".to_owned());
+        tokio::spawn(async move { GitHub::new(Client::default()).run(host, tx).await });
+        let mut results = Vec::new();
+        while let Some(r) = rx.recv().await {
+            results.extend(r)
+        }
+        assert!(!results.is_empty());
+    }
+
+    #[ignore]
+    #[tokio::test]
+    async fn handle_no_results() {
+        let (tx, _rx) = channel(1);
+        let host = Arc::new("anVubmxpa2VzdGVh.com".to_string());
+        assert!(matches!(
+            GitHub::new(Client::default())
+                .run(host, tx)
+                .await
+                .err()
+                .unwrap(),
+            VitaError::SourceError(_)
+        ));
+    }
+}
@@ -1,12 +1,38 @@
 use crate::error::{Result, VitaError};
-use crate::{DataSource, IntoSubdomain};
+use crate::{DataSource, IntoSubdomain, Subdomain};
 use async_trait::async_trait;
+use dotenv::dotenv;
 use reqwest::Client;
+use std::env;
 use std::sync::Arc;
 use tokio::sync::mpsc::Sender;
 use tracing::{info, trace, warn};
 
-const API_ERROR: &str = "error check your search parameter";
+// HackerTarget returns these as plain text in place of results, so they need to be matched
+// against the lowercased response body.
+const QUOTA_ERRORS: [&str; 2] = ["api count exceeded", "increase quota"];
+const AUTH_ERRORS: [&str; 1] = ["invalid api key"];
+const API_ERRORS: [&str; 4] = [
+    "error check your search parameter",
+    "error invalid host",
+    "error getting results",
+    "no records found",
+];
+
+// The key is optional, but the free api only allows a handful of requests a day.
+struct Creds {
+    api_key: String,
+}
+
+impl Creds {
+    pub fn read_creds() -> Result<Self> {
+        dotenv().ok();
+        match env::var("HACKERTARGET_KEY") {
+            Ok(api_key) => Ok(Self { api_key }),
+            Err(_) => Err(VitaError::UnsetKeys(vec!["HACKERTARGET_KEY".into()])),
+        }
+    }
+}
 
 struct HTResult {
     items: String,
@@ -16,13 +42,38 @@ impl HTResult {
     fn new(items: String) -> Self {
         HTResult { items }
     }
+
+    // Maps the error messages which HackerTarget sends instead of results to an error.
+    fn check_errors(&self) -> Result<()> {
+        let body = self.items.trim().to_lowercase();
+        if QUOTA_ERRORS.iter().any(|e| body.contains(e)) {
+            Err(VitaError::QuotaExceeded("HackerTarget".into()))
+        } else if AUTH_ERRORS.iter().any(|e| body.contains(e)) {
+            Err(VitaError::AuthError("HackerTarget".into()))
+        } else if body.is_empty() || API_ERRORS.iter().any(|e| body.starts_with(e)) {
+            Err(VitaError::SourceError("HackerTarget".into()))
+        } else {
+            Ok(())
+        }
+    }
 }
 
+// Each line of the hostsearch output is `hostname,ip`.
 impl IntoSubdomain for HTResult {
-    fn subdomains(&self) -> Vec<String> {
+    fn subdomains(&self) -> Vec<Subdomain> {
         self.items
             .lines()
-            .map(|s| s.split(',').collect::<Vec<&str>>()[0].to_owned())
+            .filter_map(|line| {
+                let mut parts = line.splitn(2, ',');
+                let host = parts.next()?.trim();
+                if host.is_empty() {
+                    return None;
+                }
+                match parts.next() {
+                    Some(ip) => Some(Subdomain::with_ip(host, ip)),
+                    None => Some(Subdomain::from(host)),
+                }
+            })
             .collect()
     }
 }
@@ -37,27 +88,35 @@ impl HackerTarget {
         Self { client }
     }
 
-    fn build_url(&self, host: &str) -> String {
-        format!("https://api.hackertarget.com/hostsearch/?q={}", host)
+    fn build_url(&self, host: &str, api_key: Option<&str>) -> String {
+        match api_key {
+            Some(key) => format!(
+                "https://api.hackertarget.com/hostsearch/?q={}&apikey={}",
+                host, key
+            ),
+            None => format!("https://api.hackertarget.com/hostsearch/?q={}", host),
+        }
     }
 }
 
 #[async_trait]
 impl DataSource for HackerTarget {
-    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<String>>) -> Result<()> {
+    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<Subdomain>>) -> Result<()> {
         trace!("fetching data from hackertarget for: {}", &host);
-        let uri = self.build_url(&host);
+        let api_key = Creds::read_creds().ok().map(|c| c.api_key);
+        let uri = self.build_url(&host, api_key.as_deref());
         let resp: String = self.client.get(&uri).send().await?.text().await?;
+        let result = HTResult::new(resp);
 
-        if resp != API_ERROR {
-            let subdomains = HTResult::new(resp).subdomains();
-            info!("Discovered {} results for: {}", &subdomains.len(), &host);
-            let _ = tx.send(subdomains).await;
-            return Ok(());
+        if let Err(e) = result.check_errors() {
+            warn!("no results found for {} from HackerTarget: {}", &host, e);
+            return Err(e);
         }
 
-        warn!("no results found for {} from HackerTarget", &host);
-        Err(VitaError::SourceError("HackerTarget".into()))
+        let subdomains = result.subdomains();
+        info!("Discovered {} results for: {}", &subdomains.len(), &host);
+        let _ = tx.send(subdomains).await;
+        Ok(())
     }
 }
 
@@ -67,6 +126,45 @@ mod tests {
     use matches::matches;
     use tokio::sync::mpsc::channel;
 
+    #[test]
+    fn url_builder() {
+        let correct_uri = "https://api.hackertarget.com/hostsearch/?q=hackerone.com";
+        assert_eq!(
+            correct_uri,
+            HackerTarget::default().build_url("hackerone.com", None)
+        );
+    }
+
+    #[test]
+    fn parses_hosts_and_ips() {
+        let result = HTResult::new("api.hackerone.com,104.16.99.52\nwww.hackerone.com,\n".into());
+        assert_eq!(
+            result.subdomains(),
+            vec![
+                Subdomain::with_ip("api.hackerone.com", "104.16.99.52"),
+                Subdomain::from("www.hackerone.com")
+            ]
+        );
+    }
+
+    #[test]
+    fn detects_errors() {
+        let quota = HTResult::new("API count exceeded - Increase Quota with Membership".into());
+        assert!(matches!(
+            quota.check_errors().err().unwrap(),
+            VitaError::QuotaExceeded(_)
+        ));
+
+        let invalid = HTResult::new("error check your search parameter".into());
+        assert!(matches!(
+            invalid.check_errors().err().unwrap(),
+            VitaError::SourceError(_)
+        ));
+
+        let ok = HTResult::new("api.hackerone.com,104.16.99.52".into());
+        assert!(ok.check_errors().is_ok());
+    }
+
     // Checks to see if the run function returns subdomains
     #[tokio::test]
     async fn returns_results() {
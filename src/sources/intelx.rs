@@ -1,14 +1,32 @@
 use crate::error::{Result, VitaError};
-use crate::{DataSource, IntoSubdomain};
+use crate::{DataSource, IntoSubdomain, Subdomain};
 use async_trait::async_trait;
 use dotenv::dotenv;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::env;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::mpsc::Sender;
 use tracing::{debug, info, trace, warn};
 
+// The number of results to ask for on each page
+const PAGE_LIMIT: usize = 1000;
+// How many times to poll a search which hasn't got results yet before giving up
+const MAX_POLLS: usize = 8;
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(8);
+
+// The `status` field of a search result page
+// There are results, and there might be more if we ask again
+const STATUS_RESULTS: usize = 0;
+// The search has finished and there are no more results
+const STATUS_FINISHED: usize = 1;
+// The search id wasn't found
+const STATUS_NOT_FOUND: usize = 2;
+// There are no results yet but the search is still running
+const STATUS_PENDING: usize = 3;
+
 struct Creds {
     url: String,
     api_key: String,
@@ -68,10 +86,10 @@ struct IntelxResults {
 }
 
 impl IntoSubdomain for IntelxResults {
-    fn subdomains(&self) -> Vec<String> {
+    fn subdomains(&self) -> Vec<Subdomain> {
         self.selectors
             .iter()
-            .map(|s| s.selectorvalue.to_owned())
+            .map(|s| Subdomain::from(&s.selectorvalue))
             .collect()
     }
 }
@@ -97,14 +115,22 @@ impl Intelx {
             format!("https://{}/phonebook/search?k={}", intelx_url, api_key)
         } else {
             format!(
-                "https://{}/phonebook/search/result?k={}&id={}&limit=100000",
+                "https://{}/phonebook/search/result?k={}&id={}&limit={}",
                 intelx_url,
                 api_key,
-                search_id.unwrap()
+                search_id.unwrap(),
+                PAGE_LIMIT
             )
         }
     }
 
+    fn terminate_url(&self, intelx_url: &str, api_key: &str, search_id: &str) -> String {
+        format!(
+            "https://{}/intelligent/search/terminate?k={}&id={}",
+            intelx_url, api_key, search_id
+        )
+    }
+
     async fn get_searchid(&self, host: Arc<String>) -> Result<String> {
         trace!("getting intelx searchid");
         let creds = match Creds::read_creds() {
@@ -126,11 +152,79 @@ impl Intelx {
         debug!("searchid: {:?}", &search_id);
         Ok(search_id.id)
     }
+
+    // Fetches pages of results until the search has finished, backing off while it's still
+    // running without results. Returns the number of results sent.
+    async fn poll_results(
+        &self,
+        creds: &Creds,
+        search_id: &str,
+        tx: &mut Sender<Vec<Subdomain>>,
+    ) -> Result<usize> {
+        let uri = self.build_url(&creds.url, &creds.api_key, false, Some(search_id));
+        let mut total = 0;
+        let mut polls = 0;
+        let mut backoff = INITIAL_BACKOFF;
+
+        loop {
+            let resp = self.client.get(&uri).send().await?;
+            if resp.status().is_client_error() {
+                warn!("got status: {} for intelx", resp.status().as_str());
+                return Err(VitaError::AuthError("Intelx".into()));
+            }
+
+            let page: IntelxResults = resp.json().await?;
+            let subdomains = page.subdomains();
+            debug!(
+                "intelx page with status {} had {} results",
+                page.status,
+                subdomains.len()
+            );
+
+            match page.status {
+                STATUS_RESULTS if !subdomains.is_empty() => {
+                    total += subdomains.len();
+                    let _ = tx.send(subdomains).await;
+                    backoff = INITIAL_BACKOFF;
+                }
+                STATUS_RESULTS | STATUS_PENDING => {
+                    if polls >= MAX_POLLS {
+                        warn!("gave up waiting on intelx search {}", search_id);
+                        break;
+                    }
+                    polls += 1;
+                    tokio::time::delay_for(backoff).await;
+                    backoff = std::cmp::min(backoff * 2, MAX_BACKOFF);
+                }
+                STATUS_FINISHED | STATUS_NOT_FOUND => {
+                    if !subdomains.is_empty() {
+                        total += subdomains.len();
+                        let _ = tx.send(subdomains).await;
+                    }
+                    break;
+                }
+                status => {
+                    warn!("unexpected intelx search status: {}", status);
+                    break;
+                }
+            }
+        }
+
+        Ok(total)
+    }
+
+    // Stops the search so it doesn't keep running on Intelx's end once we have what we need.
+    async fn terminate(&self, creds: &Creds, search_id: &str) {
+        let uri = self.terminate_url(&creds.url, &creds.api_key, search_id);
+        if let Err(e) = self.client.get(&uri).send().await {
+            warn!("couldn't terminate intelx search {}: {}", search_id, e);
+        }
+    }
 }
 
 #[async_trait]
 impl DataSource for Intelx {
-    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<String>>) -> Result<()> {
+    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<Subdomain>>) -> Result<()> {
         trace!("fetching data from intelx for: {}", &host);
         let creds = match Creds::read_creds() {
             Ok(creds) => creds,
@@ -138,20 +232,13 @@ impl DataSource for Intelx {
         };
 
         let search_id = self.get_searchid(host.clone()).await?;
-        let uri = self.build_url(&creds.url, &creds.api_key, false, Some(&search_id));
-        let resp = self.client.get(&uri).send().await?;
+        let polled = self.poll_results(&creds, &search_id, &mut tx).await;
+        self.terminate(&creds, &search_id).await;
 
-        if resp.status().is_client_error() {
-            warn!("got status: {} for intelx", resp.status().as_str());
-            return Err(VitaError::AuthError("Intelx".into()));
-        } else {
-            let resp: IntelxResults = resp.json().await?;
-            let subdomains = resp.subdomains();
-            if !subdomains.is_empty() {
-                info!("Discovered {} results for: {}", &subdomains.len(), &host);
-                let _ = tx.send(subdomains).await;
-                return Ok(());
-            }
+        let total = polled?;
+        if total > 0 {
+            info!("Discovered {} results for: {}", total, &host);
+            return Ok(());
         }
 
         warn!("no results for {} from Intelx", &host);
@@ -165,6 +252,20 @@ mod tests {
     use matches::matches;
     use tokio::sync::mpsc::channel;
 
+    #[test]
+    fn url_builder() {
+        let correct_uri = "https://2.intelx.io/phonebook/search/result?k=key&id=1234&limit=1000";
+        assert_eq!(
+            correct_uri,
+            Intelx::default().build_url("2.intelx.io", "key", false, Some("1234"))
+        );
+        let correct_uri = "https://2.intelx.io/intelligent/search/terminate?k=key&id=1234";
+        assert_eq!(
+            correct_uri,
+            Intelx::default().terminate_url("2.intelx.io", "key", "1234")
+        );
+    }
+
     #[tokio::test]
     #[ignore]
     async fn search_id() {
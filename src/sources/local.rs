@@ -0,0 +1,489 @@
+use crate::error::{Result, VitaError};
+use crate::{DataSource, Subdomain};
+use async_trait::async_trait;
+use dotenv::dotenv;
+use flate2::read::GzDecoder;
+use regex::Regex;
+use rusqlite::{params, Connection, OpenFlags};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use tokio::sync::mpsc::Sender;
+use tokio::sync::Mutex;
+use tokio::task;
+use tracing::{debug, info, trace, warn};
+
+// Finds names in a `subdomains` table, `?1` is the host and `?2` matches anything under it. A
+// second column, if there is one, is read as the address the name points at.
+const DEFAULT_QUERY: &str =
+    "SELECT name FROM subdomains WHERE name = ?1 OR name LIKE ?2 ESCAPE '\\'";
+
+// Splits a comma separated list of paths from the environment.
+fn paths_from_env(key: &str) -> Vec<PathBuf> {
+    env::var(key)
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(PathBuf::from)
+        .collect()
+}
+
+fn normalize(name: &str) -> String {
+    name.trim().trim_end_matches('.').to_lowercase()
+}
+
+// Whether `name` is the host or one of its subdomains.
+fn is_match(name: &str, host: &str) -> bool {
+    name == host
+        || (name.len() > host.len()
+            && name.ends_with(host)
+            && name.as_bytes()[name.len() - host.len() - 1] == b'.')
+}
+
+// The targets which `name` is, or is a subdomain of.
+fn matching_targets<'a>(name: &'a str, targets: &'a HashSet<String>) -> Vec<&'a str> {
+    std::iter::once(name)
+        .chain(name.match_indices('.').map(move |(i, _)| &name[i + 1..]))
+        .filter(|suffix| targets.contains(*suffix))
+        .collect()
+}
+
+// Escapes the `LIKE` wildcards in a host, so an `_` in a name only matches itself.
+fn escape_like(host: &str) -> String {
+    host.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+// Reads every file in the directories as vita output, with a result on each line and optionally
+// the address after a comma when it was run with `--ips`.
+fn read_outputs(dirs: &[PathBuf]) -> Result<Vec<Subdomain>> {
+    let mut seen = HashSet::new();
+    for dir in dirs.iter() {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if !path.is_file() {
+                continue;
+            }
+
+            debug!("reading previous results from {:?}", &path);
+            for line in BufReader::new(File::open(&path)?).lines() {
+                let line = line?;
+                let mut parts = line.splitn(2, ',');
+                let name = normalize(parts.next().unwrap_or(""));
+                if name.is_empty() {
+                    continue;
+                }
+
+                seen.insert(match parts.next() {
+                    Some(ip) => Subdomain::with_ip(name, ip),
+                    None => Subdomain::from(name),
+                });
+            }
+        }
+    }
+
+    Ok(seen.into_iter().collect())
+}
+
+// A line of a Rapid7 forward dns dump.
+#[derive(Deserialize, Debug)]
+struct FdnsRecord {
+    name: String,
+    #[serde(rename = "type")]
+    record_type: String,
+    value: String,
+}
+
+// Scans a forward dns dump, gzipped or not, for names under any of the targets. The dumps are far
+// too big to hold in memory, or to read again for every host, so this is a single pass over the
+// file which sorts the names by the targets they're under.
+fn search_fdns(path: &Path, targets: &HashSet<String>) -> Result<HashMap<String, Vec<Subdomain>>> {
+    let mut found: HashMap<String, Vec<Subdomain>> = HashMap::new();
+    if targets.is_empty() {
+        return Ok(found);
+    }
+
+    let file = File::open(path)?;
+    let reader: Box<dyn Read> = match path.extension() {
+        Some(ext) if ext == "gz" => Box::new(GzDecoder::new(file)),
+        _ => Box::new(file),
+    };
+
+    let escaped: Vec<String> = targets.iter().map(|t| regex::escape(t)).collect();
+    let prefilter = Regex::new(&escaped.join("|")).unwrap();
+    for line in BufReader::new(reader).lines() {
+        let line = line?;
+        // Most lines are for other domains, so skip them before paying for the json parsing
+        if !prefilter.is_match(&line) {
+            continue;
+        }
+
+        let record: FdnsRecord = match serde_json::from_str(&line) {
+            Ok(r) => r,
+            Err(_) => continue,
+        };
+
+        let name = normalize(&record.name);
+        for target in matching_targets(&name, targets) {
+            let subdomains = found.entry(target.to_string()).or_default();
+            match record.record_type.as_str() {
+                "a" | "aaaa" => subdomains.push(Subdomain::with_ip(&name, &record.value)),
+                "cname" => {
+                    let cname = normalize(&record.value);
+                    if is_match(&cname, target) {
+                        subdomains.push(Subdomain::from(cname));
+                    }
+                    subdomains.push(Subdomain::from(&name));
+                }
+                _ => subdomains.push(Subdomain::from(&name)),
+            }
+        }
+    }
+
+    Ok(found)
+}
+
+fn search_sqlite(path: &Path, query: &str, host: &str) -> Result<Vec<Subdomain>> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let mut stmt = conn.prepare(query)?;
+    let has_ip = stmt.column_count() > 1;
+    let rows = stmt.query_map(params![host, format!("%.{}", escape_like(host))], |row| {
+        let name: String = row.get(0)?;
+        let ip: Option<String> = if has_ip { row.get(1)? } else { None };
+        Ok((name, ip))
+    })?;
+
+    let mut subdomains = Vec::new();
+    for row in rows {
+        let (name, ip) = row?;
+        let name = normalize(&name);
+        subdomains.push(match ip {
+            Some(ip) => Subdomain::with_ip(name, &ip),
+            None => Subdomain::from(name),
+        });
+    }
+
+    Ok(subdomains)
+}
+
+/// `Local` reads subdomains collected earlier instead of asking a remote service. It can search
+/// directories of previous vita output, Rapid7 style forward dns dumps and a SQLite database.
+#[derive(Clone)]
+pub struct Local {
+    // `LOCAL_OUTPUT_DIRS` in the environment or a `.env` file, comma separated.
+    dirs: Vec<PathBuf>,
+    // `LOCAL_FDNS_FILES`, comma separated.
+    fdns: Vec<PathBuf>,
+    // `LOCAL_SQLITE_DB` and `LOCAL_SQLITE_QUERY`.
+    sqlite: Option<PathBuf>,
+    query: String,
+    // The previous results, read once and shared between every host.
+    outputs: Arc<Mutex<Option<Arc<Vec<Subdomain>>>>>,
+    // Every host in the run, so the forward dns dumps are only read once for all of them.
+    targets: Arc<RwLock<HashSet<String>>>,
+    // The names found in the forward dns dumps for each host which has been searched for.
+    fdns_results: Arc<Mutex<HashMap<String, Vec<Subdomain>>>>,
+}
+
+impl Default for Local {
+    fn default() -> Self {
+        Self {
+            dirs: Vec::new(),
+            fdns: Vec::new(),
+            sqlite: None,
+            query: DEFAULT_QUERY.to_string(),
+            outputs: Arc::new(Mutex::new(None)),
+            targets: Arc::new(RwLock::new(HashSet::new())),
+            fdns_results: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+impl Local {
+    pub fn new() -> Self {
+        dotenv().ok();
+        Self {
+            dirs: paths_from_env("LOCAL_OUTPUT_DIRS"),
+            fdns: paths_from_env("LOCAL_FDNS_FILES"),
+            sqlite: env::var("LOCAL_SQLITE_DB").ok().map(PathBuf::from),
+            query: env::var("LOCAL_SQLITE_QUERY").unwrap_or_else(|_| DEFAULT_QUERY.to_string()),
+            outputs: Arc::new(Mutex::new(None)),
+            targets: Arc::new(RwLock::new(HashSet::new())),
+            fdns_results: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Adds a directory of previous vita output to search.
+    pub fn output_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.dirs.push(dir.into());
+        self
+    }
+
+    /// Adds a forward dns dump to search, it's decompressed on the fly if it ends in `.gz`.
+    pub fn fdns_file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.fdns.push(path.into());
+        self
+    }
+
+    /// Sets the SQLite database to search.
+    pub fn sqlite<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.sqlite = Some(path.into());
+        self
+    }
+
+    /// Sets the query run against the SQLite database. `?1` is bound to the host and `?2` to a
+    /// `LIKE` pattern matching its subdomains, with wildcards in the host escaped by `\`.
+    pub fn sqlite_query(mut self, query: &str) -> Self {
+        self.query = query.to_string();
+        self
+    }
+
+    /// Whether any local data has been configured.
+    pub fn has_data(&self) -> bool {
+        !self.dirs.is_empty() || !self.fdns.is_empty() || self.sqlite.is_some()
+    }
+
+    // Returns the previous results, reading them the first time they're needed.
+    async fn outputs(&self) -> Result<Arc<Vec<Subdomain>>> {
+        let mut outputs = self.outputs.lock().await;
+        if let Some(subdomains) = outputs.as_ref() {
+            return Ok(Arc::clone(subdomains));
+        }
+
+        let dirs = self.dirs.clone();
+        let subdomains = Arc::new(task::spawn_blocking(move || read_outputs(&dirs)).await??);
+        debug!("read {} previous results", subdomains.len());
+        *outputs = Some(Arc::clone(&subdomains));
+        Ok(subdomains)
+    }
+
+    async fn search_outputs(&self, host: &str) -> Result<Vec<Subdomain>> {
+        let outputs = self.outputs().await?;
+        Ok(outputs
+            .iter()
+            .filter(|s| is_match(&s.host, host))
+            .cloned()
+            .collect())
+    }
+
+    // Returns the names under the host in the forward dns dumps. The first lookup reads the dumps
+    // for every host in the run, later ones only read them again for hosts which weren't known
+    // up front.
+    async fn search_fdns(&self, host: &str) -> Result<Vec<Subdomain>> {
+        let mut found = self.fdns_results.lock().await;
+        if !found.contains_key(host) {
+            let mut targets: HashSet<String> = self
+                .targets
+                .read()
+                .unwrap()
+                .iter()
+                .filter(|t| !found.contains_key(*t))
+                .cloned()
+                .collect();
+            targets.insert(host.to_string());
+            debug!("searching forward dns dumps for {} hosts", targets.len());
+
+            for path in self.fdns.iter() {
+                let p = path.clone();
+                let t = targets.clone();
+                match task::spawn_blocking(move || search_fdns(&p, &t)).await? {
+                    Ok(matches) => {
+                        for (target, subdomains) in matches.into_iter() {
+                            found.entry(target).or_default().extend(subdomains);
+                        }
+                    }
+                    Err(e) => warn!("couldn't search forward dns dump {:?}: {}", path, e),
+                }
+            }
+
+            for target in targets.into_iter() {
+                found.entry(target).or_default();
+            }
+        }
+
+        Ok(found.get(host).cloned().unwrap_or_default())
+    }
+}
+
+#[async_trait]
+impl DataSource for Local {
+    fn prepare(&self, hosts: &HashSet<String>) {
+        let mut targets = self.targets.write().unwrap();
+        targets.extend(hosts.iter().map(|h| h.to_lowercase()));
+    }
+
+    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<Subdomain>>) -> Result<()> {
+        trace!("searching local data for: {}", &host);
+        let target = host.to_lowercase();
+        let mut results = Vec::new();
+
+        if !self.dirs.is_empty() {
+            results.push(("previous results", self.search_outputs(&target).await));
+        }
+
+        if !self.fdns.is_empty() {
+            results.push(("forward dns dumps", self.search_fdns(&target).await));
+        }
+
+        if let Some(path) = &self.sqlite {
+            let path = path.clone();
+            let query = self.query.clone();
+            let target = target.clone();
+            let res = task::spawn_blocking(move || search_sqlite(&path, &query, &target)).await?;
+            results.push(("sqlite database", res));
+        }
+
+        let mut total = 0;
+        for (kind, res) in results.into_iter() {
+            match res {
+                Ok(subdomains) if !subdomains.is_empty() => {
+                    total += subdomains.len();
+                    let _ = tx.send(subdomains).await;
+                }
+                Ok(_) => {}
+                Err(e) => warn!("couldn't search {} for {}: {}", kind, &host, e),
+            }
+        }
+
+        if total > 0 {
+            info!("Discovered {} results for: {}", total, &host);
+            return Ok(());
+        }
+
+        warn!("no results for {} from Local", &host);
+        Err(VitaError::SourceError("Local".into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use matches::matches;
+    use tokio::sync::mpsc::channel;
+
+    fn fixtures() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/local")
+    }
+
+    fn hosts(mut subdomains: Vec<Subdomain>) -> Vec<String> {
+        subdomains.sort_by(|a, b| a.host.cmp(&b.host));
+        subdomains.into_iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn matches_subdomains() {
+        assert!(is_match("hackerone.com", "hackerone.com"));
+        assert!(is_match("api.hackerone.com", "hackerone.com"));
+        assert!(!is_match("nothackerone.com", "hackerone.com"));
+        assert!(!is_match("hackerone.com.evil.io", "hackerone.com"));
+    }
+
+    #[test]
+    fn reads_previous_outputs() {
+        let subdomains = read_outputs(&[fixtures().join("outputs")]).unwrap();
+        let matched: Vec<Subdomain> = subdomains
+            .into_iter()
+            .filter(|s| is_match(&s.host, "hackerone.com"))
+            .collect();
+        assert_eq!(
+            hosts(matched),
+            vec![
+                "api.hackerone.com,104.16.99.52",
+                "docs.hackerone.com",
+                "www.hackerone.com"
+            ]
+        );
+    }
+
+    #[test]
+    fn matches_targets() {
+        let targets: HashSet<String> = vec!["hackerone.com".into(), "api.hackerone.com".into()]
+            .into_iter()
+            .collect();
+        let mut matched = matching_targets("v1.api.hackerone.com", &targets);
+        matched.sort();
+        assert_eq!(matched, vec!["api.hackerone.com", "hackerone.com"]);
+        assert!(matching_targets("hackerone.com.evil.io", &targets).is_empty());
+    }
+
+    #[test]
+    fn searches_fdns_dump() {
+        let targets: HashSet<String> = vec!["hackerone.com".into(), "bugcrowd.com".into()]
+            .into_iter()
+            .collect();
+        let mut found = search_fdns(&fixtures().join("fdns.json.gz"), &targets).unwrap();
+        assert_eq!(
+            hosts(found.remove("hackerone.com").unwrap()),
+            vec![
+                "api.hackerone.com,104.16.99.52",
+                "docs.hackerone.com",
+                "links.hackerone.com",
+                "mta-sts.hackerone.com"
+            ]
+        );
+        assert_eq!(
+            hosts(found.remove("bugcrowd.com").unwrap()),
+            vec!["bugcrowd.com,104.18.6.148"]
+        );
+    }
+
+    #[test]
+    fn searches_sqlite() {
+        let path = fixtures().join("subdomains.sqlite");
+        let subdomains = search_sqlite(&path, DEFAULT_QUERY, "hackerone.com").unwrap();
+        assert_eq!(
+            hosts(subdomains),
+            vec!["events.hackerone.com", "hackerone.com"]
+        );
+
+        let with_ips =
+            "SELECT name, ip FROM subdomains WHERE name = ?1 OR name LIKE ?2 ESCAPE '\\'";
+        let subdomains = search_sqlite(&path, with_ips, "hackerone.com").unwrap();
+        assert_eq!(
+            hosts(subdomains),
+            vec!["events.hackerone.com,104.16.100.52", "hackerone.com"]
+        );
+
+        // `_` isn't a wildcard, so this doesn't match events.hackerone.com
+        let subdomains = search_sqlite(&path, DEFAULT_QUERY, "hacker_ne.com").unwrap();
+        assert!(subdomains.is_empty());
+    }
+
+    #[tokio::test]
+    async fn returns_results() {
+        let (tx, mut rx) = channel(3);
+        let host = Arc::new("hackerone.com".to_owned());
+        let local = Local::default()
+            .output_dir(fixtures().join("outputs"))
+            .fdns_file(fixtures().join("fdns.json.gz"))
+            .sqlite(fixtures().join("subdomains.sqlite"));
+        let targets = vec!["hackerone.com".to_string(), "bugcrowd.com".to_string()];
+        local.prepare(&targets.into_iter().collect());
+        local.run(host, tx).await.unwrap();
+        // Both hosts were found in the single read of the dump
+        assert!(local.fdns_results.lock().await.contains_key("bugcrowd.com"));
+
+        let mut results = Vec::new();
+        while let Some(r) = rx.recv().await {
+            results.extend(r)
+        }
+        assert_eq!(results.len(), 9);
+    }
+
+    #[tokio::test]
+    async fn handle_no_results() {
+        let (tx, _rx) = channel(3);
+        let host = Arc::new("anVubmxpa2VzdGVh.com".to_string());
+        let local = Local::default().output_dir(fixtures().join("outputs"));
+        assert!(matches!(
+            local.run(host, tx).await.err().unwrap(),
+            VitaError::SourceError(_)
+        ));
+    }
+}
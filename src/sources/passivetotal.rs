@@ -1,14 +1,18 @@
 use crate::error::{Result, VitaError};
-use crate::{DataSource, IntoSubdomain};
+use crate::{DataSource, IntoSubdomain, Subdomain};
 use async_trait::async_trait;
 use dotenv::dotenv;
 use reqwest::header::ACCEPT;
-use reqwest::Client;
-use serde::{Deserialize, Serialize};
+use reqwest::{Client, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
 use std::env;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicI64, Ordering};
 use std::sync::Arc;
 use tokio::sync::mpsc::Sender;
-use tracing::{info, trace, warn};
+use tokio::sync::Mutex;
+use tracing::{debug, info, trace, warn};
 
 struct Creds {
     key: String,
@@ -30,88 +34,241 @@ impl Creds {
     }
 }
 
-#[derive(Serialize)]
-struct Query {
-    query: String,
-}
-
-impl Query {
-    fn new(host: &str) -> Self {
-        Self {
-            query: host.to_string(),
-        }
-    }
-}
+const API_URL: &str = "https://api.passivetotal.org/v2";
 
 #[derive(Debug, Deserialize)]
 struct PassiveTotalResult {
+    #[serde(default)]
     success: bool,
     #[serde(rename = "primaryDomain")]
     primary_domain: String,
+    #[serde(default)]
     subdomains: Vec<String>,
 }
 
 impl IntoSubdomain for PassiveTotalResult {
-    fn subdomains(&self) -> Vec<String> {
+    fn subdomains(&self) -> Vec<Subdomain> {
         self.subdomains
             .iter()
-            .map(|s| format!("{}.{}", s, self.primary_domain))
+            .map(|s| Subdomain::from(format!("{}.{}", s, self.primary_domain)))
+            .collect()
+    }
+}
+
+// The unique resolutions for a domain from passive dns, these are a mix of ip addresses and the
+// hostnames from CNAME records.
+#[derive(Debug, Deserialize)]
+struct UniqueResolutions {
+    #[serde(rename = "queryValue")]
+    query_value: String,
+    #[serde(default)]
+    results: Vec<String>,
+}
+
+impl IntoSubdomain for UniqueResolutions {
+    fn subdomains(&self) -> Vec<Subdomain> {
+        self.results
+            .iter()
+            .map(|r| match r.parse::<IpAddr>() {
+                Ok(_) => Subdomain::with_ip(self.query_value.as_str(), r),
+                Err(_) => Subdomain::from(r.trim_end_matches('.')),
+            })
             .collect()
     }
 }
 
-#[derive(Default, Clone)]
+#[derive(Debug, Deserialize)]
+struct QuotaCounts {
+    search_api: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UserQuota {
+    counts: QuotaCounts,
+    limits: QuotaCounts,
+    next_reset: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AccountQuota {
+    user: UserQuota,
+}
+
+// The search api quota for the account, as it was when we first checked it.
+#[derive(Debug, Clone, PartialEq)]
+struct Quota {
+    used: i64,
+    limit: i64,
+    next_reset: Option<String>,
+}
+
+impl From<AccountQuota> for Quota {
+    fn from(quota: AccountQuota) -> Self {
+        Self {
+            used: quota.user.counts.search_api.unwrap_or_default(),
+            limit: quota.user.limits.search_api.unwrap_or_default(),
+            next_reset: quota.user.next_reset,
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct PassiveTotal {
     client: Client,
+    // Fetched on the first run so we can skip requests once the quota is used up. The inner
+    // `None` means the fetch failed, so it isn't tried again.
+    quota: Arc<Mutex<Option<Option<Quota>>>>,
+    // The number of search api requests made since the quota was fetched.
+    requests: Arc<AtomicI64>,
+}
+
+impl Default for PassiveTotal {
+    fn default() -> Self {
+        Self::new(Client::default())
+    }
 }
 
 impl PassiveTotal {
     pub fn new(client: Client) -> Self {
-        Self { client }
+        Self {
+            client,
+            quota: Arc::new(Mutex::new(None)),
+            requests: Arc::new(AtomicI64::new(0)),
+        }
+    }
+
+    fn build_url(&self, host: &str) -> String {
+        format!("{}/enrichment/subdomains?query={}", API_URL, host)
+    }
+
+    fn unique_url(&self, host: &str) -> String {
+        format!("{}/dns/passive/unique?query={}", API_URL, host)
     }
 
-    fn build_url(&self) -> String {
-        "https://api.passivetotal.org/v2/enrichment/subdomains".to_string()
+    fn quota_url(&self) -> String {
+        format!("{}/account/quota", API_URL)
+    }
+
+    // Sends an authenticated request to the api, mapping the status codes PassiveTotal uses for
+    // bad credentials and exhausted quotas.
+    async fn get<T: DeserializeOwned>(&self, creds: &Creds, uri: &str) -> Result<T> {
+        let resp = self
+            .client
+            .get(uri)
+            .basic_auth(&creds.key, Some(&creds.secret))
+            .header(ACCEPT, "application/json")
+            .send()
+            .await?;
+
+        match resp.status() {
+            StatusCode::PAYMENT_REQUIRED | StatusCode::TOO_MANY_REQUESTS => {
+                Err(VitaError::QuotaExceeded("PassiveTotal".into()))
+            }
+            s if s.is_client_error() => {
+                warn!("got status: {} from passivetotal", s.as_str());
+                Err(VitaError::AuthError("PassiveTotal".into()))
+            }
+            _ => Ok(resp.json().await?),
+        }
+    }
+
+    // Returns the quota for the account, fetching it the first time it's needed. The lock is
+    // held across the fetch so hosts running at the same time wait for a single request.
+    async fn quota(&self, creds: &Creds) -> Option<Quota> {
+        let mut quota = self.quota.lock().await;
+        if let Some(cached) = quota.as_ref() {
+            return cached.clone();
+        }
+
+        let fetched = match self.get::<AccountQuota>(creds, &self.quota_url()).await {
+            Ok(account) => {
+                let fetched = Quota::from(account);
+                debug!("passivetotal quota: {:?}", &fetched);
+                Some(fetched)
+            }
+            Err(e) => {
+                debug!("couldn't get the passivetotal quota: {}", e);
+                None
+            }
+        };
+        *quota = Some(fetched.clone());
+        fetched
+    }
+
+    fn remaining(&self, quota: &Quota) -> i64 {
+        quota.limit - quota.used - self.requests.load(Ordering::SeqCst)
+    }
+
+    async fn search<T: DeserializeOwned>(&self, creds: &Creds, uri: &str) -> Result<T> {
+        self.requests.fetch_add(1, Ordering::SeqCst);
+        self.get(creds, uri).await
     }
 }
 
 #[async_trait]
 impl DataSource for PassiveTotal {
-    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<String>>) -> Result<()> {
+    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<Subdomain>>) -> Result<()> {
         trace!("fetching data from passivetotal for: {}", &host);
         let creds = match Creds::from_env() {
             Ok(c) => c,
             Err(e) => return Err(e),
         };
 
-        let uri = self.build_url();
-        let query = Query::new(&host);
-        let resp = self
-            .client
-            .get(&uri)
-            .basic_auth(&creds.key, Some(&creds.secret))
-            .header(ACCEPT, "application/json")
-            .json(&query)
-            .send()
-            .await?;
-
-        if resp.status().is_client_error() {
-            warn!("got status: {} from passivetotal", resp.status().as_str());
-            return Err(VitaError::AuthError("Passivetotal".into()));
-        } else {
-            let resp: PassiveTotalResult = resp.json().await?;
-            let subdomains = resp.subdomains();
-
-            if !subdomains.is_empty() {
-                info!("Discovered {} results for: {}", &subdomains.len(), &host);
-                let _ = tx.send(subdomains).await;
-                return Ok(());
+        // The quota endpoint isn't available on every plan, so only skip the requests when we
+        // know there's nothing left.
+        if let Some(quota) = self.quota(&creds).await {
+            if quota.limit > 0 && self.remaining(&quota) <= 0 {
+                warn!("passivetotal quota has been used up");
+                return Err(VitaError::QuotaExceeded("PassiveTotal".into()));
             }
         }
 
+        let enrichment: PassiveTotalResult = self.search(&creds, &self.build_url(&host)).await?;
+        if !enrichment.success {
+            warn!("passivetotal subdomain enrichment failed for: {}", &host);
+            return Err(VitaError::SourceError("PassiveTotal".into()));
+        }
+
+        let mut subdomains = enrichment.subdomains();
+        match self
+            .search::<UniqueResolutions>(&creds, &self.unique_url(&host))
+            .await
+        {
+            Ok(unique) => subdomains.extend(unique.subdomains()),
+            Err(e) => warn!("couldn't get passive dns from passivetotal: {}", e),
+        }
+
+        if !subdomains.is_empty() {
+            info!("Discovered {} results for: {}", &subdomains.len(), &host);
+            let _ = tx.send(subdomains).await;
+            return Ok(());
+        }
+
         warn!("no results for {} from PassiveTotal", &host);
         Err(VitaError::SourceError("PassiveTotal".into()))
     }
+
+    fn notes(&self) -> Vec<String> {
+        let quota = self
+            .quota
+            .try_lock()
+            .ok()
+            .and_then(|quota| quota.clone().flatten());
+        match quota.as_ref() {
+            Some(quota) => {
+                let mut note = format!(
+                    "search api quota: {} of {} used",
+                    quota.limit - self.remaining(quota),
+                    quota.limit
+                );
+                if let Some(reset) = &quota.next_reset {
+                    note.push_str(&format!(", resets at {}", reset));
+                }
+                vec![note]
+            }
+            None => Vec::new(),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -120,6 +277,49 @@ mod tests {
     use matches::matches;
     use tokio::sync::mpsc::channel;
 
+    #[test]
+    fn url_builder() {
+        let pt = PassiveTotal::default();
+        assert_eq!(
+            pt.build_url("hackerone.com"),
+            "https://api.passivetotal.org/v2/enrichment/subdomains?query=hackerone.com"
+        );
+        assert_eq!(
+            pt.unique_url("hackerone.com"),
+            "https://api.passivetotal.org/v2/dns/passive/unique?query=hackerone.com"
+        );
+    }
+
+    #[test]
+    fn unique_resolutions() {
+        let unique: UniqueResolutions = serde_json::from_str(
+            r#"{"queryValue": "hackerone.com", "results": ["104.16.99.52", "hackerone.map.fastly.net."]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            unique.subdomains(),
+            vec![
+                Subdomain::with_ip("hackerone.com", "104.16.99.52"),
+                Subdomain::from("hackerone.map.fastly.net"),
+            ]
+        );
+    }
+
+    #[test]
+    fn quota_notes() {
+        let account: AccountQuota = serde_json::from_str(
+            r#"{"user": {"counts": {"search_api": 5}, "limits": {"search_api": 15}, "next_reset": "2020-11-01 00:00:00"}}"#,
+        )
+        .unwrap();
+        let pt = PassiveTotal::default();
+        *pt.quota.try_lock().unwrap() = Some(Some(Quota::from(account)));
+        pt.requests.fetch_add(2, Ordering::SeqCst);
+        assert_eq!(
+            pt.notes(),
+            vec!["search api quota: 7 of 15 used, resets at 2020-11-01 00:00:00"]
+        );
+    }
+
     // Checks to see if the run function returns subdomains
     #[tokio::test]
     #[ignore]
@@ -0,0 +1,361 @@
+use crate::error::{Result, VitaError};
+use crate::{find_hosts, host_regex, DataSource, Subdomain};
+use async_trait::async_trait;
+use regex::Regex;
+use reqwest::Client;
+use scraper::{Html, Selector};
+use std::collections::HashSet;
+use std::sync::Arc;
+use tokio::sync::mpsc::Sender;
+use tracing::{debug, info, trace, warn};
+use url::Url;
+
+/// How a site splits its results across pages.
+#[derive(Debug, Clone)]
+pub enum Pagination {
+    /// Everything is on a single page.
+    Single,
+    /// `{page}` in the url template is replaced with `first`, `first + step` and so on, for at
+    /// most `max` pages.
+    Pages {
+        first: usize,
+        step: usize,
+        max: usize,
+    },
+    /// The next page is linked from the `href` of the element matching `selector`, followed for
+    /// at most `max` pages.
+    NextLink { selector: String, max: usize },
+}
+
+/// Describes how to scrape subdomains from a site which lists them as html.
+#[derive(Debug, Clone)]
+pub struct ScrapeDefinition {
+    /// The name used in logs and errors.
+    pub name: String,
+    /// The url of the first page, `{domain}` is replaced with the host being searched and
+    /// `{page}` with the page number when paginating by page.
+    pub url: String,
+    pub pagination: Pagination,
+    /// Only the text of the elements matching this selector is searched for hostnames, the whole
+    /// page is searched if it's not set.
+    pub selector: Option<String>,
+    /// The regex used to find hostnames, `{domain}` is replaced with the escaped host. Defaults to
+    /// any hostname under the host.
+    pub pattern: Option<String>,
+}
+
+impl ScrapeDefinition {
+    /// Creates a definition which searches the whole of a single page.
+    pub fn new(name: &str, url: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            url: url.to_string(),
+            pagination: Pagination::Single,
+            selector: None,
+            pattern: None,
+        }
+    }
+
+    /// Sets how the results are paginated.
+    pub fn pagination(mut self, pagination: Pagination) -> Self {
+        self.pagination = pagination;
+        self
+    }
+
+    /// Sets the css selector of the elements holding the results.
+    pub fn selector(mut self, selector: &str) -> Self {
+        self.selector = Some(selector.to_string());
+        self
+    }
+
+    /// Sets the regex used to find hostnames.
+    pub fn pattern(mut self, pattern: &str) -> Self {
+        self.pattern = Some(pattern.to_string());
+        self
+    }
+
+    /// RapidDNS lists 100 subdomains a page in its results table.
+    pub fn rapiddns() -> Self {
+        Self::new(
+            "RapidDns",
+            "https://rapiddns.io/subdomain/{domain}?page={page}",
+        )
+        .pagination(Pagination::Pages {
+            first: 1,
+            step: 1,
+            max: 10,
+        })
+        .selector("#table tbody td")
+    }
+
+    /// SiteDossier lists 100 sites a page, with the page given by the offset of its first result.
+    pub fn sitedossier() -> Self {
+        Self::new(
+            "SiteDossier",
+            "http://www.sitedossier.com/parentdomain/{domain}/{page}",
+        )
+        .pagination(Pagination::Pages {
+            first: 1,
+            step: 100,
+            max: 10,
+        })
+        .selector("ol li a")
+    }
+
+    /// DNSRepo links to every subdomain it knows about from a single page.
+    pub fn dnsrepo() -> Self {
+        Self::new("DnsRepo", "https://dnsrepo.noc.org/?domain={domain}").selector("table td a")
+    }
+
+    fn first_url(&self, host: &str) -> String {
+        self.page_url(host, 0)
+    }
+
+    // The url of the `n`th page when paginating by page, counting from 0.
+    fn page_url(&self, host: &str, n: usize) -> String {
+        let page = match self.pagination {
+            Pagination::Pages { first, step, .. } => first + n * step,
+            _ => 1,
+        };
+
+        self.url
+            .replace("{domain}", host)
+            .replace("{page}", &page.to_string())
+    }
+
+    fn max_pages(&self) -> usize {
+        match self.pagination {
+            Pagination::Single => 1,
+            Pagination::Pages { max, .. } | Pagination::NextLink { max, .. } => max,
+        }
+    }
+
+    fn host_regex(&self, host: &str) -> Result<Regex> {
+        let pattern = match &self.pattern {
+            Some(p) => p.replace("{domain}", &regex::escape(host)),
+            None => return Ok(host_regex(host)),
+        };
+
+        Regex::new(&pattern).map_err(|e| {
+            warn!("invalid pattern for {}: {}", &self.name, e);
+            VitaError::SourceError(self.name.clone())
+        })
+    }
+
+    // Returns the hostnames on a page and the link to the next one, if it has one.
+    fn extract(&self, re: &Regex, base: &str, body: &str) -> (HashSet<String>, Option<String>) {
+        let html = Html::parse_document(body);
+        let text: Vec<String> = match self.selector.as_deref().map(Selector::parse) {
+            Some(Ok(selector)) => html
+                .select(&selector)
+                .map(|e| e.text().collect::<String>())
+                .collect(),
+            Some(Err(_)) => {
+                warn!("invalid selector for {}", &self.name);
+                Vec::new()
+            }
+            None => vec![body.to_string()],
+        };
+
+        let hosts = text.iter().flat_map(|t| find_hosts(re, t)).collect();
+
+        let next = match &self.pagination {
+            Pagination::NextLink { selector, .. } => Selector::parse(selector)
+                .ok()
+                .and_then(|s| html.select(&s).next())
+                .and_then(|e| e.value().attr("href"))
+                .and_then(|href| Url::parse(base).ok()?.join(href).ok())
+                .map(|u| u.to_string()),
+            _ => None,
+        };
+
+        (hosts, next)
+    }
+}
+
+/// `Scraper` is a `DataSource` for sites which publish their results as html, described by a
+/// `ScrapeDefinition`.
+#[derive(Clone)]
+pub struct Scraper {
+    client: Client,
+    definition: Arc<ScrapeDefinition>,
+}
+
+impl Scraper {
+    pub fn new(client: Client, definition: ScrapeDefinition) -> Self {
+        Self {
+            client,
+            definition: Arc::new(definition),
+        }
+    }
+
+    async fn fetch(&self, uri: &str) -> Result<String> {
+        let resp = self.client.get(uri).send().await?;
+        if !resp.status().is_success() {
+            warn!(
+                "got status: {} from {}",
+                resp.status().as_str(),
+                &self.definition.name
+            );
+            return Err(VitaError::SourceError(self.definition.name.clone()));
+        }
+
+        Ok(resp.text().await?)
+    }
+}
+
+#[async_trait]
+impl DataSource for Scraper {
+    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<Subdomain>>) -> Result<()> {
+        let def = &self.definition;
+        trace!("fetching data from {} for: {}", &def.name, &host);
+        let re = def.host_regex(&host)?;
+        let mut seen = HashSet::new();
+        let mut uri = def.first_url(&host);
+
+        for n in 0..def.max_pages() {
+            let body = match self.fetch(&uri).await {
+                Ok(body) => body,
+                Err(e) if !seen.is_empty() => {
+                    debug!("stopped paging {} at page {}: {}", &def.name, n + 1, e);
+                    break;
+                }
+                Err(e) => return Err(e),
+            };
+
+            let (hosts, next) = def.extract(&re, &uri, &body);
+            let subdomains: Vec<Subdomain> = hosts
+                .into_iter()
+                .filter(|h| seen.insert(h.clone()))
+                .map(Subdomain::from)
+                .collect();
+
+            // Sites tend to repeat the last page, or show an empty one, past the end
+            if subdomains.is_empty() {
+                break;
+            }
+            let _ = tx.send(subdomains).await;
+
+            uri = match (&def.pagination, next) {
+                (Pagination::Pages { .. }, _) => def.page_url(&host, n + 1),
+                (Pagination::NextLink { .. }, Some(next)) => next,
+                _ => break,
+            };
+        }
+
+        if !seen.is_empty() {
+            info!("Discovered {} results for: {}", seen.len(), &host);
+            return Ok(());
+        }
+
+        warn!("no results for {} from {}", &host, &def.name);
+        Err(VitaError::SourceError(def.name.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use matches::matches;
+    use tokio::sync::mpsc::channel;
+
+    fn extract(def: &ScrapeDefinition, body: &str) -> (Vec<String>, Option<String>) {
+        let re = def.host_regex("hackerone.com").unwrap();
+        let (hosts, next) = def.extract(&re, &def.first_url("hackerone.com"), body);
+        let mut hosts: Vec<String> = hosts.into_iter().collect();
+        hosts.sort();
+        (hosts, next)
+    }
+
+    #[test]
+    fn url_builder() {
+        let def = ScrapeDefinition::sitedossier();
+        assert_eq!(
+            def.first_url("hackerone.com"),
+            "http://www.sitedossier.com/parentdomain/hackerone.com/1"
+        );
+        assert_eq!(
+            def.page_url("hackerone.com", 2),
+            "http://www.sitedossier.com/parentdomain/hackerone.com/201"
+        );
+        assert_eq!(
+            ScrapeDefinition::dnsrepo().first_url("hackerone.com"),
+            "https://dnsrepo.noc.org/?domain=hackerone.com"
+        );
+    }
+
+    #[test]
+    fn parses_rapiddns() {
+        let body = include_str!("../../tests/fixtures/scrape/rapiddns.html");
+        let (hosts, _) = extract(&ScrapeDefinition::rapiddns(), body);
+        assert_eq!(
+            hosts,
+            vec![
+                "api.hackerone.com",
+                "docs.hackerone.com",
+                "mta-sts.managed.hackerone.com"
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_sitedossier() {
+        let body = include_str!("../../tests/fixtures/scrape/sitedossier.html");
+        let (hosts, _) = extract(&ScrapeDefinition::sitedossier(), body);
+        assert_eq!(hosts, vec!["support.hackerone.com", "www.hackerone.com"]);
+    }
+
+    #[test]
+    fn parses_dnsrepo() {
+        let body = include_str!("../../tests/fixtures/scrape/dnsrepo.html");
+        let (hosts, _) = extract(&ScrapeDefinition::dnsrepo(), body);
+        assert_eq!(hosts, vec!["events.hackerone.com", "go.hackerone.com"]);
+    }
+
+    #[test]
+    fn follows_next_link() {
+        let def = ScrapeDefinition::new("Test", "https://example.com/search/{domain}")
+            .pagination(Pagination::NextLink {
+                selector: "a.next".to_string(),
+                max: 5,
+            })
+            .pattern(r"[a-z]+\.{domain}");
+        let body = r#"<p>www.hackerone.com</p><a class="next" href="?page=2">next</a>"#;
+        let (hosts, next) = extract(&def, body);
+        assert_eq!(hosts, vec!["www.hackerone.com"]);
+        assert_eq!(
+            next.unwrap(),
+            "https://example.com/search/hackerone.com?page=2"
+        );
+    }
+
+    #[ignore]
+    #[tokio::test]
+    async fn returns_results() {
+        let (tx, mut rx) = channel(10);
+        let host = Arc::new("hackerone.com".to_owned());
+        let _ = Scraper::new(Client::default(), ScrapeDefinition::rapiddns())
+            .run(host, tx)
+            .await;
+        let mut results = Vec::new();
+        for r in rx.recv().await {
+            results.extend(r)
+        }
+        assert!(!results.is_empty());
+    }
+
+    #[ignore]
+    #[tokio::test]
+    async fn handle_no_results() {
+        let (tx, _rx) = channel(10);
+        let host = Arc::new("anVubmxpa2VzdGVh.com".to_string());
+        assert!(matches!(
+            Scraper::new(Client::default(), ScrapeDefinition::dnsrepo())
+                .run(host, tx)
+                .await
+                .err()
+                .unwrap(),
+            VitaError::SourceError(_)
+        ));
+    }
+}
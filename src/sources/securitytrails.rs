@@ -1,13 +1,16 @@
 use crate::error::{Result, VitaError};
-use crate::{DataSource, IntoSubdomain};
+use crate::{DataSource, IntoSubdomain, Subdomain};
 use async_trait::async_trait;
 use dotenv::dotenv;
-use reqwest::Client;
-use serde::Deserialize;
+use reqwest::{Client, RequestBuilder, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::env;
 use std::sync::Arc;
 use tokio::sync::mpsc::Sender;
-use tracing::{info, trace, warn};
+use tokio::sync::Mutex;
+use tracing::{debug, info, trace, warn};
 
 struct Creds {
     api_key: String,
@@ -23,43 +26,331 @@ impl Creds {
     }
 }
 
+// Options for the SecurityTrails source, read from the environment or a `.env` file.
+#[derive(Clone, Default)]
+struct Config {
+    // Only return direct children of the host
+    children_only: bool,
+    // Include subdomains which no longer resolve
+    include_inactive: bool,
+    // Also look up domains associated with the host, such as those owned by the same org
+    associated: bool,
+    // The most api calls we're allowed to make on a single key during a run
+    max_requests: Option<i64>,
+}
+
+impl Config {
+    fn from_env() -> Self {
+        dotenv().ok();
+        let flag = |name: &str| {
+            env::var(name)
+                .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+                .unwrap_or(false)
+        };
+
+        Self {
+            children_only: flag("SECURITY_TRAILS_CHILDREN_ONLY"),
+            include_inactive: flag("SECURITY_TRAILS_INCLUDE_INACTIVE"),
+            associated: flag("SECURITY_TRAILS_ASSOCIATED"),
+            max_requests: env::var("SECURITY_TRAILS_MAX_REQUESTS")
+                .ok()
+                .and_then(|v| v.parse().ok()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct SubdomainsMeta {
+    #[serde(default)]
+    limit_reached: bool,
+}
+
 #[derive(Debug, Deserialize, Default)]
 struct SecTrailsResult {
     subdomains: Vec<String>,
+    #[serde(default)]
+    meta: SubdomainsMeta,
     #[serde(skip)]
     host: Arc<String>,
 }
 
 impl IntoSubdomain for SecTrailsResult {
-    fn subdomains(&self) -> Vec<String> {
+    fn subdomains(&self) -> Vec<Subdomain> {
         self.subdomains
             .iter()
-            .map(|s| format!("{}.{}", s, self.host))
+            .map(|s| Subdomain::from(format!("{}.{}", s, self.host)))
+            .collect()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Record {
+    hostname: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ScrollQuery {
+    query: String,
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct ScrollMeta {
+    scroll_id: Option<String>,
+}
+
+// Shared by the DSL search and the scroll endpoint used to page through it.
+#[derive(Debug, Deserialize)]
+struct ScrollResult {
+    #[serde(default)]
+    records: Vec<Record>,
+    #[serde(default)]
+    meta: ScrollMeta,
+}
+
+impl IntoSubdomain for ScrollResult {
+    fn subdomains(&self) -> Vec<Subdomain> {
+        self.records
+            .iter()
+            .map(|r| Subdomain::from(&r.hostname))
+            .collect()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct AssociatedMeta {
+    total_pages: i32,
+}
+
+#[derive(Debug, Deserialize)]
+struct AssociatedResult {
+    #[serde(default)]
+    records: Vec<Record>,
+    meta: AssociatedMeta,
+}
+
+impl IntoSubdomain for AssociatedResult {
+    fn subdomains(&self) -> Vec<Subdomain> {
+        self.records
+            .iter()
+            .map(|r| Subdomain::related(&r.hostname))
             .collect()
     }
 }
 
-#[derive(Default, Clone)]
+#[derive(Debug, Deserialize)]
+struct Usage {
+    current_monthly_usage: i64,
+    allowed_monthly_usage: i64,
+}
+
+/// Keeps track of how many calls each api key has left, so a long list of hosts can't burn
+/// through a month's worth of credits in one run.
+#[derive(Default)]
+struct QuotaGuard {
+    remaining: HashMap<String, i64>,
+}
+
+#[derive(Clone)]
 pub struct SecurityTrails {
     client: Client,
+    config: Config,
+    quota: Arc<Mutex<QuotaGuard>>,
+}
+
+impl Default for SecurityTrails {
+    fn default() -> Self {
+        Self {
+            client: Client::default(),
+            config: Config::default(),
+            quota: Arc::new(Mutex::new(QuotaGuard::default())),
+        }
+    }
 }
 
 impl SecurityTrails {
     pub fn new(client: Client) -> Self {
-        Self { client }
+        Self {
+            client,
+            config: Config::from_env(),
+            quota: Arc::new(Mutex::new(QuotaGuard::default())),
+        }
+    }
+
+    /// Only return subdomains which are direct children of the host
+    pub fn children_only(mut self, children_only: bool) -> Self {
+        self.config.children_only = children_only;
+        self
+    }
+
+    /// Include subdomains which are no longer active
+    pub fn include_inactive(mut self, include_inactive: bool) -> Self {
+        self.config.include_inactive = include_inactive;
+        self
+    }
+
+    /// Also return domains associated with the host
+    pub fn associated(mut self, associated: bool) -> Self {
+        self.config.associated = associated;
+        self
+    }
+
+    /// Sets the maximum number of api calls to make with a key during a run
+    pub fn max_requests(mut self, max: i64) -> Self {
+        self.config.max_requests = Some(max);
+        self
     }
 
     fn build_url(&self, host: &str) -> String {
         format!(
-            "https://api.securitytrails.com/v1/domain/{}/subdomains",
-            host
+            "https://api.securitytrails.com/v1/domain/{}/subdomains\
+            ?children_only={}&include_inactive={}",
+            host, self.config.children_only, self.config.include_inactive
         )
     }
+
+    fn scroll_url(&self, scroll_id: Option<&str>) -> String {
+        match scroll_id {
+            Some(id) => format!("https://api.securitytrails.com/v1/scroll/{}", id),
+            None => "https://api.securitytrails.com/v1/domains/list?include_ips=false&scroll=true"
+                .to_string(),
+        }
+    }
+
+    fn associated_url(&self, host: &str, page: i32) -> String {
+        format!(
+            "https://api.securitytrails.com/v1/domain/{}/associated?page={}",
+            host, page
+        )
+    }
+
+    // Takes a call from the key's quota, checking how much is left on the account first if
+    // this is the first time we've seen the key.
+    async fn acquire(&self, api_key: &str) -> Result<()> {
+        let mut quota = self.quota.lock().await;
+        let remaining = match quota.remaining.get(api_key) {
+            Some(r) => *r,
+            None => {
+                let resp = self
+                    .client
+                    .get("https://api.securitytrails.com/v1/account/usage")
+                    .header("apikey", api_key)
+                    .send()
+                    .await?;
+                if !resp.status().is_success() {
+                    warn!(
+                        "got status: {} from security trails",
+                        resp.status().as_str()
+                    );
+                    return Err(VitaError::AuthError("SecurityTrails".into()));
+                }
+
+                let usage: Usage = resp.json().await?;
+                debug!("securitytrails usage: {:?}", &usage);
+
+                let left = usage.allowed_monthly_usage - usage.current_monthly_usage;
+                match self.config.max_requests {
+                    Some(max) => left.min(max),
+                    None => left,
+                }
+            }
+        };
+
+        if remaining <= 0 {
+            warn!("securitytrails quota exhausted, skipping request");
+            quota.remaining.insert(api_key.to_owned(), 0);
+            return Err(VitaError::QuotaExceeded("SecurityTrails".into()));
+        }
+
+        quota.remaining.insert(api_key.to_owned(), remaining - 1);
+        Ok(())
+    }
+
+    async fn send<T: DeserializeOwned>(&self, api_key: &str, req: RequestBuilder) -> Result<T> {
+        self.acquire(api_key).await?;
+        let resp = req.header("apikey", api_key).send().await?;
+
+        if resp.status() == StatusCode::TOO_MANY_REQUESTS {
+            warn!(
+                "got status: {} from security trails",
+                resp.status().as_str()
+            );
+            return Err(VitaError::QuotaExceeded("SecurityTrails".into()));
+        } else if resp.status().is_client_error() {
+            warn!(
+                "got status: {} from security trails",
+                resp.status().as_str()
+            );
+            return Err(VitaError::AuthError("SecurityTrails".into()));
+        }
+
+        Ok(resp.json().await?)
+    }
+
+    // Pages through the DSL search for every hostname under the apex domain, for hosts with
+    // more subdomains than the subdomains endpoint will return.
+    async fn scroll(
+        &self,
+        api_key: &str,
+        host: &str,
+        tx: &mut Sender<Vec<Subdomain>>,
+    ) -> Result<usize> {
+        let query = ScrollQuery {
+            query: format!("apex_domain = '{}'", host),
+        };
+        let req = self.client.post(&self.scroll_url(None)).json(&query);
+        let mut page: ScrollResult = self.send(api_key, req).await?;
+        let mut total = 0;
+
+        loop {
+            let subdomains = page.subdomains();
+            if subdomains.is_empty() {
+                break;
+            }
+            total += subdomains.len();
+            let _ = tx.send(subdomains).await;
+
+            let scroll_id = match page.meta.scroll_id {
+                Some(id) => id,
+                None => break,
+            };
+            let req = self.client.get(&self.scroll_url(Some(&scroll_id)));
+            page = self.send(api_key, req).await?;
+        }
+
+        Ok(total)
+    }
+
+    async fn associated_domains(
+        &self,
+        api_key: &str,
+        host: &str,
+        tx: &mut Sender<Vec<Subdomain>>,
+    ) -> Result<usize> {
+        let mut total = 0;
+        let mut page = 1;
+
+        loop {
+            let req = self.client.get(&self.associated_url(host, page));
+            let resp: AssociatedResult = self.send(api_key, req).await?;
+            let domains = resp.subdomains();
+            total += domains.len();
+            if !domains.is_empty() {
+                let _ = tx.send(domains).await;
+            }
+
+            if page >= resp.meta.total_pages {
+                break;
+            }
+            page += 1;
+        }
+
+        Ok(total)
+    }
 }
 
 #[async_trait]
 impl DataSource for SecurityTrails {
-    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<String>>) -> Result<()> {
+    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<Subdomain>>) -> Result<()> {
         trace!("fetching data from securitytrails for: {}", &host);
 
         let api_key = match Creds::read_creds() {
@@ -68,29 +359,40 @@ impl DataSource for SecurityTrails {
         };
 
         let uri = self.build_url(&host);
-        let resp = self
-            .client
-            .get(&uri)
-            .header("apikey", api_key)
-            .send()
-            .await?;
-
-        if resp.status().is_client_error() {
-            warn!(
-                "got status: {} from security trails",
-                resp.status().as_str()
+        let mut data: SecTrailsResult = self.send(&api_key, self.client.get(&uri)).await?;
+        data.host = host.clone();
+        let subdomains = data.subdomains();
+        let mut total = subdomains.len();
+        if !subdomains.is_empty() {
+            let _ = tx.send(subdomains).await;
+        }
+
+        if data.meta.limit_reached {
+            debug!(
+                "securitytrails limit reached for {}, scrolling instead",
+                &host
             );
-            return Err(VitaError::AuthError("SecurityTrails".into()));
-        } else {
-            let resp: Option<SecTrailsResult> = resp.json().await?;
-            if let Some(data) = resp {
-                let subdomains = data.subdomains();
-                info!("Discovered {} results for: {}", &subdomains.len(), &host);
-                let _ = tx.send(subdomains).await;
-                return Ok(());
+            match self.scroll(&api_key, &host, &mut tx).await {
+                Ok(n) => total += n,
+                Err(e) => warn!(
+                    "couldn't scroll securitytrails results for {}: {}",
+                    &host, e
+                ),
+            }
+        }
+
+        if self.config.associated {
+            match self.associated_domains(&api_key, &host, &mut tx).await {
+                Ok(n) => total += n,
+                Err(e) => warn!("couldn't get associated domains for {}: {}", &host, e),
             }
         }
 
+        if total > 0 {
+            info!("Discovered {} results for: {}", total, &host);
+            return Ok(());
+        }
+
         warn!("no results for {} from SecurityTrails", &host);
         Err(VitaError::SourceError("SecurityTrails".into()))
     }
@@ -104,22 +406,48 @@ mod tests {
 
     #[test]
     fn url_builder() {
-        let correct_uri = "https://api.securitytrails.com/v1/domain/hackerone.com/subdomains";
+        let correct_uri = "https://api.securitytrails.com/v1/domain/hackerone.com/subdomains\
+            ?children_only=false&include_inactive=false";
         assert_eq!(
             correct_uri,
             SecurityTrails::default().build_url("hackerone.com")
         );
     }
 
+    #[test]
+    fn url_builder_options() {
+        let correct_uri = "https://api.securitytrails.com/v1/domain/hackerone.com/subdomains\
+            ?children_only=true&include_inactive=true";
+        assert_eq!(
+            correct_uri,
+            SecurityTrails::default()
+                .children_only(true)
+                .include_inactive(true)
+                .build_url("hackerone.com")
+        );
+    }
+
+    // The guard shouldn't make any requests once the key has nothing left.
+    #[tokio::test]
+    async fn quota_guard_stops_requests() {
+        let st = SecurityTrails::default();
+        st.quota.lock().await.remaining.insert("key".into(), 1);
+        assert!(st.acquire("key").await.is_ok());
+        assert!(matches!(
+            st.acquire("key").await.err().unwrap(),
+            VitaError::QuotaExceeded(_)
+        ));
+    }
+
     // Checks to see if the run function returns subdomains
     #[ignore]
     #[tokio::test]
     async fn returns_results() {
         let (tx, mut rx) = channel(1);
         let host = Arc::new("hackerone.com".to_owned());
-        let _ = SecurityTrails::default().run(host, tx).await;
+        tokio::spawn(async move { SecurityTrails::default().run(host, tx).await });
         let mut results = Vec::new();
-        for r in rx.recv().await {
+        while let Some(r) = rx.recv().await {
             results.extend(r)
         }
         assert!(!results.is_empty());
@@ -0,0 +1,253 @@
+use crate::error::{Result, VitaError};
+use crate::{DataSource, IntoSubdomain, Subdomain};
+use async_trait::async_trait;
+use dotenv::dotenv;
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use std::env;
+use std::sync::Arc;
+use tokio::sync::mpsc::Sender;
+use tracing::{debug, info, trace, warn};
+
+// The most pages fetched for a single domain, every page after the first costs a query credit
+const DEFAULT_MAX_PAGES: u32 = 10;
+
+struct Creds {
+    key: String,
+}
+
+impl Creds {
+    fn read_creds() -> Result<Self> {
+        dotenv().ok();
+        match env::var("SHODAN_KEY") {
+            Ok(key) => Ok(Self { key }),
+            Err(_) => Err(VitaError::UnsetKeys(vec!["SHODAN_KEY".into()])),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+struct Record {
+    subdomain: String,
+    #[serde(rename = "type")]
+    record_type: String,
+    value: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct ShodanResult {
+    domain: String,
+    #[serde(default)]
+    data: Vec<Record>,
+    #[serde(default)]
+    more: bool,
+}
+
+impl ShodanResult {
+    fn fqdn(&self, record: &Record) -> String {
+        if record.subdomain.is_empty() {
+            self.domain.clone()
+        } else {
+            format!("{}.{}", record.subdomain, self.domain)
+        }
+    }
+}
+
+impl IntoSubdomain for ShodanResult {
+    // Every host keeps the record it was found in, and A and AAAA records keep the address they
+    // point at too. CNAME targets are returned on their own as well since they often point at
+    // infrastructure worth knowing about, those in another zone are marked as related.
+    fn subdomains(&self) -> Vec<Subdomain> {
+        let mut subdomains = Vec::with_capacity(self.data.len());
+        for record in self.data.iter() {
+            let value = record.value.trim_end_matches('.');
+            let mut subdomain =
+                Subdomain::with_record(self.fqdn(record), &record.record_type, value);
+            let target = match record.record_type.as_str() {
+                "A" | "AAAA" => {
+                    subdomain.ip = value.parse().ok();
+                    None
+                }
+                "CNAME"
+                    if value == self.domain || value.ends_with(&format!(".{}", self.domain)) =>
+                {
+                    Some(Subdomain::from(value))
+                }
+                "CNAME" => Some(Subdomain::related(value)),
+                _ => None,
+            };
+            subdomains.push(subdomain);
+            subdomains.extend(target);
+        }
+
+        subdomains
+    }
+}
+
+#[derive(Clone)]
+pub struct Shodan {
+    client: Client,
+    // `SHODAN_MAX_PAGES` in the environment or a `.env` file.
+    max_pages: u32,
+}
+
+impl Default for Shodan {
+    fn default() -> Self {
+        Self {
+            client: Client::default(),
+            max_pages: DEFAULT_MAX_PAGES,
+        }
+    }
+}
+
+impl Shodan {
+    pub fn new(client: Client) -> Self {
+        dotenv().ok();
+        let max_pages = env::var("SHODAN_MAX_PAGES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MAX_PAGES);
+
+        Self { client, max_pages }
+    }
+
+    /// Sets the maximum number of pages to fetch for each domain.
+    pub fn max_pages(mut self, pages: u32) -> Self {
+        self.max_pages = pages;
+        self
+    }
+
+    fn build_url(&self, host: &str, api_key: &str, page: u32) -> String {
+        format!(
+            "https://api.shodan.io/dns/domain/{}?key={}&page={}",
+            host, api_key, page
+        )
+    }
+
+    async fn page(&self, uri: &str) -> Result<ShodanResult> {
+        let resp = self.client.get(uri).send().await?;
+        match resp.status() {
+            StatusCode::TOO_MANY_REQUESTS => Err(VitaError::QuotaExceeded("Shodan".into())),
+            // Shodan returns a 404 for domains it doesn't know about
+            StatusCode::NOT_FOUND => Err(VitaError::SourceError("Shodan".into())),
+            s if s.is_client_error() => {
+                warn!("got status: {} from shodan", s.as_str());
+                Err(VitaError::AuthError("Shodan".into()))
+            }
+            _ => Ok(resp.json().await?),
+        }
+    }
+}
+
+#[async_trait]
+impl DataSource for Shodan {
+    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<Subdomain>>) -> Result<()> {
+        trace!("fetching data from shodan for: {}", &host);
+        let api_key = match Creds::read_creds() {
+            Ok(creds) => creds.key,
+            Err(e) => return Err(e),
+        };
+
+        let mut total = 0;
+        for page in 1..=self.max_pages {
+            let uri = self.build_url(&host, &api_key, page);
+            let resp = match self.page(&uri).await {
+                Ok(resp) => resp,
+                Err(e) if total > 0 => {
+                    warn!("couldn't fetch page {} from shodan: {}", page, e);
+                    break;
+                }
+                Err(VitaError::SourceError(_)) => break,
+                Err(e) => return Err(e),
+            };
+
+            let subdomains = resp.subdomains();
+            debug!("shodan page {} had {} results", page, subdomains.len());
+            if !subdomains.is_empty() {
+                total += subdomains.len();
+                let _ = tx.send(subdomains).await;
+            }
+
+            if !resp.more {
+                break;
+            }
+        }
+
+        if total > 0 {
+            info!("Discovered {} results for: {}", total, &host);
+            return Ok(());
+        }
+
+        warn!("no results for {} from Shodan", &host);
+        Err(VitaError::SourceError("Shodan".into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use matches::matches;
+    use tokio::sync::mpsc::channel;
+
+    #[test]
+    fn url_builder() {
+        let correct_uri = "https://api.shodan.io/dns/domain/hackerone.com?key=key&page=2";
+        assert_eq!(
+            correct_uri,
+            Shodan::default().build_url("hackerone.com", "key", 2)
+        );
+    }
+
+    #[test]
+    fn keeps_record_values() {
+        let resp: ShodanResult = serde_json::from_str(
+            r#"{"domain": "hackerone.com", "tags": [], "more": false, "data": [
+                {"subdomain": "", "type": "A", "value": "104.16.99.52", "last_seen": "2020-11-01"},
+                {"subdomain": "docs", "type": "CNAME", "value": "hacker0x01.github.io", "last_seen": "2020-11-01"},
+                {"subdomain": "www", "type": "CNAME", "value": "hackerone.com.", "last_seen": "2020-11-01"},
+                {"subdomain": "", "type": "MX", "value": "aspmx.l.google.com", "last_seen": "2020-11-01"}
+            ]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(resp.data[1].record_type, "CNAME");
+        assert_eq!(
+            resp.subdomains(),
+            vec![
+                Subdomain {
+                    ip: "104.16.99.52".parse().ok(),
+                    ..Subdomain::with_record("hackerone.com", "A", "104.16.99.52")
+                },
+                Subdomain::with_record("docs.hackerone.com", "CNAME", "hacker0x01.github.io"),
+                Subdomain::related("hacker0x01.github.io"),
+                Subdomain::with_record("www.hackerone.com", "CNAME", "hackerone.com"),
+                Subdomain::from("hackerone.com"),
+                Subdomain::with_record("hackerone.com", "MX", "aspmx.l.google.com"),
+            ]
+        );
+    }
+
+    #[ignore]
+    #[tokio::test]
+    async fn returns_results() {
+        let (tx, mut rx) = channel(1);
+        let host = Arc::new("hackerone.com".to_owned());
+        tokio::spawn(async move { Shodan::default().run(host, tx).await });
+        let mut results = Vec::new();
+        while let Some(r) = rx.recv().await {
+            results.extend(r)
+        }
+        assert!(!results.is_empty());
+    }
+
+    #[ignore]
+    #[tokio::test]
+    async fn handle_no_results() {
+        let (tx, _rx) = channel(1);
+        let host = Arc::new("anVubmxpa2VzdGVh.com".to_string());
+        assert!(matches!(
+            Shodan::default().run(host, tx).await.err().unwrap(),
+            VitaError::SourceError(_)
+        ));
+    }
+}
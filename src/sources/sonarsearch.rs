@@ -1,50 +1,212 @@
 use crate::error::{Result, VitaError};
-use crate::{DataSource, QUEUE_SIZE};
+use crate::{DataSource, Subdomain, QUEUE_SIZE};
+use addr::DomainName;
 use async_trait::async_trait;
+use crobat::crobat::Domain;
 use crobat::Crobat;
-use futures::StreamExt;
+use dotenv::dotenv;
+use futures::{Stream, StreamExt};
 use reqwest::Client;
+use std::env;
 use std::sync::Arc;
 use tokio::sync::mpsc::Sender;
-use tracing::{debug, info};
+use tokio::sync::Mutex;
+use tracing::{debug, info, trace, warn};
 
-#[derive(Default, Clone)]
+/// What SonarSearch is asked for each input
+#[derive(Clone, Debug, PartialEq)]
+enum Mode {
+    /// Subdomains of the input domain
+    Subdomains,
+    /// The same name as the input domain under other top level domains
+    Tlds,
+    /// Hostnames which resolve to the input ip address or cidr range
+    ReverseDns,
+}
+
+impl Default for Mode {
+    fn default() -> Self {
+        Self::Subdomains
+    }
+}
+
+#[derive(Clone)]
 pub struct SonarSearch {
     client: Client,
+    mode: Mode,
+    // The SonarSearch instance to query, `CROBAT_URL` in the environment or a `.env` file.
+    endpoint: String,
+    // Shared between every host so we only open one connection to SonarSearch.
+    crobat: Arc<Mutex<Option<Crobat>>>,
+}
+
+impl Default for SonarSearch {
+    fn default() -> Self {
+        Self {
+            client: Client::default(),
+            mode: Mode::default(),
+            endpoint: crobat::DEFAULT_ENDPOINT.to_string(),
+            crobat: Arc::new(Mutex::new(None)),
+        }
+    }
 }
 
 impl SonarSearch {
     pub fn new(client: Client) -> Self {
-        Self { client }
+        dotenv().ok();
+        Self {
+            client,
+            mode: Mode::Subdomains,
+            endpoint: env::var("CROBAT_URL").unwrap_or_else(|_| crobat::DEFAULT_ENDPOINT.into()),
+            crobat: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Sets the SonarSearch instance to query. `https` urls use TLS and `http` urls are
+    /// plaintext.
+    pub fn endpoint(mut self, url: &str) -> Self {
+        self.endpoint = url.to_string();
+        self
+    }
+
+    /// Looks up the name of each input domain under other top level domains
+    pub fn tlds(mut self) -> Self {
+        self.mode = Mode::Tlds;
+        self
+    }
+
+    /// Treats each input as an ip address or cidr range and looks up the hostnames for it
+    pub fn reverse_dns(mut self) -> Self {
+        self.mode = Mode::ReverseDns;
+        self
+    }
+
+    // GetTLDs takes the name without any suffix, so `api.hackerone.com` becomes `hackerone`.
+    fn org_name(host: &str) -> String {
+        let root = match host.parse::<DomainName>() {
+            Ok(d) => d.root().to_string(),
+            Err(_) => host.to_string(),
+        };
+
+        root.split('.').next().unwrap_or(&root).to_string()
+    }
+
+    // Returns a handle to the shared connection, connecting on first use.
+    async fn crobat(&self) -> Result<Crobat> {
+        let mut crobat = self.crobat.lock().await;
+        if let Some(client) = crobat.as_ref() {
+            return Ok(client.clone());
+        }
+
+        let client = Crobat::connect_to(&self.endpoint).await?;
+        *crobat = Some(client.clone());
+        Ok(client)
+    }
+}
+
+// Keeps the ipv4 SonarSearch has for the domain, if it has one.
+fn with_ipv4(d: Domain) -> Vec<Subdomain> {
+    vec![Subdomain::with_ip(d.domain, &d.ipv4)]
+}
+
+// Sends everything from a crobat stream across the channel in batches of `QUEUE_SIZE`,
+// returning how many results were sent. If the stream breaks part of the way through we still
+// send whatever we've collected before returning the error.
+async fn drain<S, T, F>(
+    mut stream: S,
+    mut tx: Sender<Vec<Subdomain>>,
+    into_subdomains: F,
+) -> Result<usize>
+where
+    S: Stream<Item = crobat::Result<T>> + Unpin,
+    F: Fn(T) -> Vec<Subdomain>,
+{
+    let mut results = Vec::with_capacity(QUEUE_SIZE);
+    let mut total = 0;
+
+    while let Some(r) = stream.next().await {
+        let item = match r {
+            Ok(item) => item,
+            Err(e) => {
+                warn!(
+                    "sonarsearch stream failed after {} results: {}",
+                    total + results.len(),
+                    e
+                );
+                if !results.is_empty() {
+                    let _ = tx.send(results).await;
+                }
+                return Err(e.into());
+            }
+        };
+        results.extend(into_subdomains(item));
+
+        if results.len() >= QUEUE_SIZE {
+            debug!("sonarsearch queue is full, sending across channel",);
+            total += results.len();
+            let _ = tx.send(results.drain(..).collect()).await;
+        }
     }
+
+    if !results.is_empty() {
+        info!(
+            "draining {} remaining items from sonarsearch queue",
+            results.len()
+        );
+        total += results.len();
+        let _ = tx.send(results.drain(..).collect()).await;
+    }
+
+    Ok(total)
 }
 
 #[async_trait]
 impl DataSource for SonarSearch {
-    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<String>>) -> Result<()> {
-        let mut results = Vec::with_capacity(QUEUE_SIZE);
-        let mut client = Crobat::connect().await?;
-        let mut subs = client.get_subs(host.clone()).await?;
-
-        while let Some(r) = subs.next().await {
-            let domain = r.map(|d| d.domain).map_err(|_| VitaError::CrobatError)?;
-            results.push(domain);
-
-            if results.len() == QUEUE_SIZE {
-                debug!("sonarsearch queue is full, sending across channel",);
-                let mut tx = tx.clone();
-                let _ = tx.send(results.drain(..).collect()).await;
+    async fn run(&self, host: Arc<String>, tx: Sender<Vec<Subdomain>>) -> Result<()> {
+        trace!("fetching data from sonarsearch for: {}", &host);
+        let mut client = self.crobat().await?;
+
+        let total = match self.mode {
+            Mode::Subdomains => {
+                let subs = client.get_subs(host.clone()).await?;
+                drain(subs, tx, with_ipv4).await?
             }
-        }
+            Mode::Tlds => {
+                let name = Arc::new(SonarSearch::org_name(&host));
+                let tlds = client.get_tlds(name).await?;
+                drain(tlds, tx, with_ipv4).await?
+            }
+            Mode::ReverseDns if host.contains('/') => {
+                let ranges = client.reverse_dns_range(host.clone()).await?;
+                drain(ranges, tx, |r| {
+                    let ip = r.ip;
+                    r.domains
+                        .into_iter()
+                        .map(|d| Subdomain::with_ip(d, &ip))
+                        .collect()
+                })
+                .await?
+            }
+            Mode::ReverseDns => {
+                let hosts = client.reverse_dns(host.clone()).await?;
+                drain(hosts, tx, |d| {
+                    let ip = if d.ipv4.is_empty() {
+                        host.as_str()
+                    } else {
+                        d.ipv4.as_str()
+                    };
+                    vec![Subdomain::with_ip(d.domain.as_str(), ip)]
+                })
+                .await?
+            }
+        };
 
-        if !results.is_empty() {
-            info!(
-                "draining {} remaining items from sonarsearch queue",
-                results.len()
-            );
-            let _ = tx.send(results.drain(..).collect()).await;
+        if total > 0 {
+            info!("Discovered {} results for: {}", total, &host);
+            return Ok(());
         }
 
+        warn!("no results for {} from SonarSearch", &host);
         Err(VitaError::SourceError("SonarSearch".into()))
     }
 }
@@ -55,6 +217,82 @@ mod tests {
     use matches::matches;
     use tokio::sync::mpsc::channel;
 
+    #[test]
+    fn default_endpoint() {
+        let sonar = SonarSearch::default();
+        assert_eq!(sonar.endpoint, "https://crobat-rpc.omnisint.io");
+        assert_eq!(
+            sonar.endpoint("http://localhost:1997").endpoint,
+            "http://localhost:1997"
+        );
+    }
+
+    #[test]
+    fn keeps_ipv4() {
+        let domain = Domain {
+            domain: "api.hackerone.com".into(),
+            ipv4: "104.16.99.52".into(),
+        };
+        assert_eq!(
+            with_ipv4(domain),
+            vec![Subdomain::with_ip("api.hackerone.com", "104.16.99.52")]
+        );
+
+        let no_ip = Domain {
+            domain: "api.hackerone.com".into(),
+            ipv4: String::new(),
+        };
+        assert_eq!(with_ipv4(no_ip)[0].ip, None);
+    }
+
+    fn domain(name: &str) -> Domain {
+        Domain {
+            domain: name.into(),
+            ipv4: String::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn drain_counts_results() {
+        let (tx, mut rx) = channel(1);
+        let stream = futures::stream::iter(vec![Ok(domain("a.hackerone.com"))]);
+        assert_eq!(drain(stream, tx, with_ipv4).await.unwrap(), 1);
+        assert_eq!(rx.recv().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn drain_empty_stream() {
+        let (tx, _rx) = channel(1);
+        let stream = futures::stream::iter(Vec::<crobat::Result<Domain>>::new());
+        assert_eq!(drain(stream, tx, with_ipv4).await.unwrap(), 0);
+    }
+
+    // Results from before the stream broke should still be sent.
+    #[tokio::test]
+    async fn drain_sends_partial_results() {
+        let (tx, mut rx) = channel(1);
+        let stream = futures::stream::iter(vec![
+            Ok(domain("a.hackerone.com")),
+            Ok(domain("b.hackerone.com")),
+            Err(crobat::CrobatError::StreamError(Box::new(
+                tonic::Status::unavailable("connection reset"),
+            ))),
+            Ok(domain("c.hackerone.com")),
+        ]);
+
+        assert!(matches!(
+            drain(stream, tx, with_ipv4).await.err().unwrap(),
+            VitaError::CrobatError(_)
+        ));
+        assert_eq!(rx.recv().await.unwrap().len(), 2);
+    }
+
+    #[test]
+    fn org_name() {
+        assert_eq!(SonarSearch::org_name("hackerone.com"), "hackerone");
+        assert_eq!(SonarSearch::org_name("api.hackerone.co.uk"), "hackerone");
+    }
+
     #[ignore]
     #[tokio::test]
     async fn returns_results() {
@@ -68,6 +306,32 @@ mod tests {
         assert!(!results.is_empty());
     }
 
+    #[ignore]
+    #[tokio::test]
+    async fn returns_tlds() {
+        let (tx, mut rx) = channel(1);
+        let host = Arc::new("hackerone.com".to_owned());
+        let _ = SonarSearch::default().tlds().run(host, tx).await;
+        let mut results = Vec::new();
+        for r in rx.recv().await {
+            results.extend(r)
+        }
+        assert!(!results.is_empty());
+    }
+
+    #[ignore]
+    #[tokio::test]
+    async fn returns_reverse_dns_range() {
+        let (tx, mut rx) = channel(1);
+        let host = Arc::new("104.16.99.0/24".to_owned());
+        let _ = SonarSearch::default().reverse_dns().run(host, tx).await;
+        let mut results = Vec::new();
+        for r in rx.recv().await {
+            results.extend(r)
+        }
+        assert!(!results.is_empty());
+    }
+
     #[ignore]
     #[tokio::test]
     async fn handle_no_results() {
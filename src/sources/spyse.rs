@@ -1,5 +1,5 @@
 use crate::error::{Result, VitaError};
-use crate::{DataSource, IntoSubdomain};
+use crate::{DataSource, IntoSubdomain, Subdomain};
 use async_trait::async_trait;
 use dotenv::dotenv;
 use reqwest::header::ACCEPT;
@@ -26,22 +26,26 @@ impl Creds {
 
 #[derive(Debug, Deserialize)]
 struct SpyseResult {
-    data: SpyseItem,
+    data: SpyseData,
 }
 
 #[derive(Debug, Deserialize)]
-struct SpyseItem {
-    items: Vec<Subdomain>,
+struct SpyseData {
+    items: Vec<SpyseItem>,
 }
 
 #[derive(Debug, Deserialize)]
-struct Subdomain {
+struct SpyseItem {
     name: String,
 }
 
 impl IntoSubdomain for SpyseResult {
-    fn subdomains(&self) -> Vec<String> {
-        self.data.items.iter().map(|i| i.name.to_owned()).collect()
+    fn subdomains(&self) -> Vec<Subdomain> {
+        self.data
+            .items
+            .iter()
+            .map(|i| Subdomain::from(&i.name))
+            .collect()
     }
 }
 
@@ -65,7 +69,7 @@ impl Spyse {
 
 #[async_trait]
 impl DataSource for Spyse {
-    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<String>>) -> Result<()> {
+    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<Subdomain>>) -> Result<()> {
         trace!("fetching data from spyse for: {}", &host);
         let token = match Creds::read_creds() {
             Ok(creds) => creds.token,
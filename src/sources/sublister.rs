@@ -1,5 +1,5 @@
 use crate::error::{Result, VitaError};
-use crate::{DataSource, IntoSubdomain};
+use crate::{DataSource, IntoSubdomain, Subdomain};
 use async_trait::async_trait;
 use reqwest::Client;
 use serde_json::value::Value;
@@ -19,10 +19,10 @@ impl SublisterResult {
 
 //TODO: can this just be collected without the map?
 impl IntoSubdomain for SublisterResult {
-    fn subdomains(&self) -> Vec<String> {
+    fn subdomains(&self) -> Vec<Subdomain> {
         self.items
             .iter()
-            .map(|s| s.as_str().unwrap().to_owned())
+            .map(|s| Subdomain::from(s.as_str().unwrap()))
             .collect()
     }
 }
@@ -44,7 +44,7 @@ impl Sublister {
 
 #[async_trait]
 impl DataSource for Sublister {
-    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<String>>) -> Result<()> {
+    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<Subdomain>>) -> Result<()> {
         trace!("fetching data from sublister for: {}", &host);
         let uri = self.build_url(&host);
         let resp: Option<Value> = self.client.get(&uri).send().await?.json().await?;
@@ -1,5 +1,5 @@
 use crate::error::{Result, VitaError};
-use crate::{DataSource, IntoSubdomain};
+use crate::{DataSource, IntoSubdomain, Subdomain};
 use async_trait::async_trait;
 use reqwest::Client;
 use serde::Deserialize;
@@ -13,11 +13,11 @@ struct ThreatCrowdResult {
 }
 
 impl IntoSubdomain for ThreatCrowdResult {
-    fn subdomains(&self) -> Vec<String> {
+    fn subdomains(&self) -> Vec<Subdomain> {
         self.subdomains
             .iter()
             .flatten()
-            .map(|s| s.to_owned())
+            .map(Subdomain::from)
             .collect()
     }
 }
@@ -42,7 +42,7 @@ impl ThreatCrowd {
 
 #[async_trait]
 impl DataSource for ThreatCrowd {
-    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<String>>) -> Result<()> {
+    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<Subdomain>>) -> Result<()> {
         trace!("fetching data from threatcrowd for: {}", &host);
         let uri = self.build_url(&host);
         let resp: ThreatCrowdResult = self.client.get(&uri).send().await?.json().await?;
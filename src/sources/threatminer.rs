@@ -1,58 +1,142 @@
 use crate::error::{Result, VitaError};
-use crate::{DataSource, IntoSubdomain};
+use crate::{DataSource, IntoSubdomain, Subdomain};
 use async_trait::async_trait;
 use reqwest::Client;
 use serde::Deserialize;
-use std::sync::Arc;
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc::Sender;
-use tracing::{info, trace, warn};
+use tracing::{debug, info, trace, warn};
+use url::Url;
+
+// The record types which contain hostnames: passive dns, uris and subdomains.
+const RECORD_TYPES: [u8; 3] = [PASSIVE_DNS, 3, 5];
+
+// Passive dns results only list the ips the queried domain resolved to.
+const PASSIVE_DNS: u8 = 2;
+
+// ThreatMiner allows 10 requests a minute, so we send one every 6 seconds.
+const REQUEST_INTERVAL: Duration = Duration::from_secs(6);
 
 #[derive(Deserialize)]
 struct ThreatminerResult {
-    results: Vec<String>,
+    #[serde(default)]
+    results: Vec<Value>,
 }
 
+// Subdomain results are plain strings, uri results are objects which may hold the hostname in a
+// `domain` field or as part of the `uri`.
 impl IntoSubdomain for ThreatminerResult {
-    //todo: does it have to be HashSet<String> or can we change to HashSet<&str>
-    fn subdomains(&self) -> Vec<String> {
-        self.results.iter().map(|s| s.to_owned()).collect()
+    fn subdomains(&self) -> Vec<Subdomain> {
+        self.results
+            .iter()
+            .flat_map(|r| match r {
+                Value::String(s) => vec![Subdomain::from(s)],
+                Value::Object(o) => {
+                    let domain = o.get("domain").and_then(Value::as_str).map(Subdomain::from);
+                    let uri = o
+                        .get("uri")
+                        .and_then(Value::as_str)
+                        .and_then(|u| Url::parse(u).ok())
+                        .and_then(|u| u.host_str().map(Subdomain::from));
+                    domain.into_iter().chain(uri).collect()
+                }
+                _ => Vec::new(),
+            })
+            .collect()
+    }
+}
+
+impl ThreatminerResult {
+    fn resolutions(&self, host: &str) -> Vec<Subdomain> {
+        self.results
+            .iter()
+            .filter_map(|r| r.get("ip").and_then(Value::as_str))
+            .map(|ip| Subdomain::with_ip(host, ip))
+            .collect()
+    }
+}
+
+/// Spaces out requests so every host shares ThreatMiner's rate limit.
+#[derive(Default)]
+struct RateLimiter {
+    next: Option<Instant>,
+}
+
+impl RateLimiter {
+    // Reserves the next free slot and returns how long to wait for it.
+    fn reserve(&mut self, now: Instant) -> Duration {
+        let slot = match self.next {
+            Some(next) if next > now => next,
+            _ => now,
+        };
+        self.next = Some(slot + REQUEST_INTERVAL);
+        slot - now
     }
 }
 
 #[derive(Default, Clone)]
 pub struct ThreatMiner {
     client: Client,
+    limiter: Arc<Mutex<RateLimiter>>,
 }
 
 impl ThreatMiner {
     pub fn new(client: Client) -> Self {
-        Self { client }
+        Self {
+            client,
+            limiter: Arc::new(Mutex::new(RateLimiter::default())),
+        }
     }
 
-    fn build_url(&self, host: &str) -> String {
+    fn build_url(&self, host: &str, record_type: u8) -> String {
         format!(
-            "https://api.threatminer.org/v2/domain.php?q={}&api=True&rt=5",
-            host
+            "https://api.threatminer.org/v2/domain.php?q={}&api=True&rt={}",
+            host, record_type
         )
     }
+
+    async fn fetch(&self, host: &str, record_type: u8) -> Result<Vec<Subdomain>> {
+        let wait = self.limiter.lock().unwrap().reserve(Instant::now());
+        if wait > Duration::from_secs(0) {
+            debug!("waiting {:?} for threatminer rate limit", wait);
+            tokio::time::delay_for(wait).await;
+        }
+
+        let uri = self.build_url(host, record_type);
+        let resp: Option<ThreatminerResult> = self.client.get(&uri).send().await?.json().await?;
+        Ok(resp
+            .map(|r| match record_type {
+                PASSIVE_DNS => r.resolutions(host),
+                _ => r.subdomains(),
+            })
+            .unwrap_or_default())
+    }
 }
 
 #[async_trait]
 impl DataSource for ThreatMiner {
-    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<String>>) -> Result<()> {
+    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<Subdomain>>) -> Result<()> {
         trace!("fetching data from threatminer for: {}", &host);
-        let uri = self.build_url(&host);
-        let resp: Option<ThreatminerResult> = self.client.get(&uri).send().await?.json().await?;
+        let mut total = 0;
 
-        if let Some(data) = resp {
-            let subdomains = data.subdomains();
-            if !subdomains.is_empty() {
-                info!("Discovered {} results for: {}", &subdomains.len(), &host);
-                let _ = tx.send(subdomains).await;
-                return Ok(());
+        for &record_type in RECORD_TYPES.iter() {
+            match self.fetch(&host, record_type).await {
+                Ok(subdomains) if !subdomains.is_empty() => {
+                    total += subdomains.len();
+                    let _ = tx.send(subdomains).await;
+                }
+                Ok(_) => {}
+                Err(e) => warn!("threatminer rt={} failed for {}: {}", record_type, &host, e),
             }
         }
 
+        if total > 0 {
+            info!("Discovered {} results for: {}", total, &host);
+            return Ok(());
+        }
+
         warn!("no results found for {} from ThreatMiner", &host);
         Err(VitaError::SourceError("ThreatMiner".into()))
     }
@@ -69,7 +153,60 @@ mod tests {
         let correct_uri = "https://api.threatminer.org/v2/domain.php?q=hackerone.com&api=True&rt=5";
         assert_eq!(
             correct_uri,
-            ThreatMiner::default().build_url("hackerone.com")
+            ThreatMiner::default().build_url("hackerone.com", 5)
+        );
+    }
+
+    #[test]
+    fn extracts_hostnames() {
+        let data: ThreatminerResult = serde_json::from_str(
+            r#"{"status_code":"200","results":[
+                "api.hackerone.com",
+                {"domain":"www.hackerone.com","ip":"1.2.3.4"},
+                {"uri":"https://docs.hackerone.com/index.html","ip":"1.2.3.4"},
+                {"ip":"1.2.3.4"}
+            ]}"#,
+        )
+        .unwrap();
+        let hosts: Vec<String> = data.subdomains().into_iter().map(|s| s.host).collect();
+        assert_eq!(
+            hosts,
+            vec![
+                "api.hackerone.com",
+                "www.hackerone.com",
+                "docs.hackerone.com"
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_passive_dns_ips() {
+        let data: ThreatminerResult = serde_json::from_str(
+            r#"{"status_code":"200","results":[
+                {"ip":"104.16.99.52","first_seen":"2019-01-01 00:00:00","last_seen":"2020-11-01 00:00:00"},
+                {"ip":"104.16.100.52","first_seen":"2019-01-01 00:00:00","last_seen":"2020-11-01 00:00:00"}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            data.resolutions("hackerone.com"),
+            vec![
+                Subdomain::with_ip("hackerone.com", "104.16.99.52"),
+                Subdomain::with_ip("hackerone.com", "104.16.100.52"),
+            ]
+        );
+    }
+
+    #[test]
+    fn rate_limiter_spaces_requests() {
+        let mut limiter = RateLimiter::default();
+        let now = Instant::now();
+        assert_eq!(limiter.reserve(now), Duration::from_secs(0));
+        assert_eq!(limiter.reserve(now), REQUEST_INTERVAL);
+        assert_eq!(limiter.reserve(now), REQUEST_INTERVAL * 2);
+        assert_eq!(
+            limiter.reserve(now + REQUEST_INTERVAL * 10),
+            Duration::from_secs(0)
         );
     }
 
@@ -78,9 +215,10 @@ mod tests {
     async fn returns_results() {
         let (tx, mut rx) = channel(1);
         let host = Arc::new("hackerone.com".to_owned());
-        let _ = ThreatMiner::default().run(host, tx).await;
+        // Every record type is sent separately, so the results are read while it runs
+        tokio::spawn(async move { ThreatMiner::default().run(host, tx).await });
         let mut results = Vec::new();
-        for r in rx.recv().await {
+        while let Some(r) = rx.recv().await {
             results.extend(r)
         }
         assert!(!results.is_empty());
@@ -1,5 +1,5 @@
 use crate::error::{Result, VitaError};
-use crate::{DataSource, IntoSubdomain};
+use crate::{DataSource, IntoSubdomain, Subdomain};
 use async_trait::async_trait;
 use reqwest::Client;
 use serde::Deserialize;
@@ -23,10 +23,10 @@ struct UrlScanResult {
 }
 
 impl IntoSubdomain for UrlScanResult {
-    fn subdomains(&self) -> Vec<String> {
+    fn subdomains(&self) -> Vec<Subdomain> {
         self.results
             .iter()
-            .map(|s| s.page.domain.to_owned())
+            .map(|s| Subdomain::from(&s.page.domain))
             .collect()
     }
 }
@@ -48,7 +48,7 @@ impl UrlScan {
 
 #[async_trait]
 impl DataSource for UrlScan {
-    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<String>>) -> Result<()> {
+    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<Subdomain>>) -> Result<()> {
         trace!("fetching data from urlscan for: {}", &host);
         let uri = self.build_url(&host);
         let resp: Option<UrlScanResult> = self.client.get(&uri).send().await?.json().await?;
@@ -1,5 +1,5 @@
 use crate::error::{Result, VitaError};
-use crate::{DataSource, IntoSubdomain};
+use crate::{DataSource, IntoSubdomain, Subdomain};
 use async_trait::async_trait;
 use reqwest::Client;
 use serde::Deserialize;
@@ -8,21 +8,21 @@ use tokio::sync::mpsc::Sender;
 use tracing::{info, trace, warn};
 
 #[derive(Deserialize)]
-struct Subdomain {
+struct VirustotalItem {
     id: String,
 }
 
 #[derive(Deserialize)]
 struct VirustotalResult {
-    data: Option<Vec<Subdomain>>,
+    data: Option<Vec<VirustotalItem>>,
 }
 
 impl IntoSubdomain for VirustotalResult {
-    fn subdomains(&self) -> Vec<String> {
+    fn subdomains(&self) -> Vec<Subdomain> {
         self.data
             .iter()
             .flatten()
-            .map(|s| s.id.to_owned())
+            .map(|s| Subdomain::from(&s.id))
             .collect()
     }
 }
@@ -50,7 +50,7 @@ impl VirusTotal {
 
 #[async_trait]
 impl DataSource for VirusTotal {
-    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<String>>) -> Result<()> {
+    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<Subdomain>>) -> Result<()> {
         trace!("fetching data from virustotal for: {}", &host);
         let uri = self.build_url(&host);
         let resp: VirustotalResult = self.client.get(&uri).send().await?.json().await?;
@@ -1,5 +1,5 @@
 use crate::error::{Result, VitaError};
-use crate::{DataSource, IntoSubdomain};
+use crate::{DataSource, IntoSubdomain, Subdomain};
 use async_trait::async_trait;
 use reqwest::Client;
 use serde_json::value::Value;
@@ -18,18 +18,19 @@ impl WaybackResult {
     }
 }
 
+/// Returns the host from an archived url, shared with the other web archive sources.
+pub(crate) fn host_from_url(url: &str) -> Option<Subdomain> {
+    Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(Subdomain::from))
+}
+
 //TODO: this could be cleaned up, to avoid creating the extra vec `vecs`
 impl IntoSubdomain for WaybackResult {
-    fn subdomains(&self) -> Vec<String> {
+    fn subdomains(&self) -> Vec<Subdomain> {
         let arr = self.data.as_array().unwrap();
         let vecs: Vec<&str> = arr.iter().map(|s| s[0].as_str().unwrap()).collect();
-        vecs.into_iter()
-            .filter_map(|a| {
-                Url::parse(a)
-                    .ok()
-                    .and_then(|u| u.host_str().map(|h| h.into()))
-            })
-            .collect()
+        vecs.into_iter().filter_map(host_from_url).collect()
     }
 }
 
@@ -54,7 +55,7 @@ impl Wayback {
 
 #[async_trait]
 impl DataSource for Wayback {
-    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<String>>) -> Result<()> {
+    async fn run(&self, host: Arc<String>, mut tx: Sender<Vec<Subdomain>>) -> Result<()> {
         trace!("fetching data from wayback for: {}", &host);
         let uri = self.build_url(&host);
         let resp: Option<Value> = self.client.get(&uri).send().await?.json().await?;
@@ -87,6 +88,15 @@ mod tests {
         assert_eq!(correct_uri, Wayback::default().build_url("hackerone.com"));
     }
 
+    #[test]
+    fn extracts_hosts() {
+        assert_eq!(
+            host_from_url("https://api.hackerone.com:443/v1/reports?page=2"),
+            Some(Subdomain::from("api.hackerone.com"))
+        );
+        assert_eq!(host_from_url("not a url"), None);
+    }
+
     #[ignore] // hangs forever on windows for some reasons?
     #[tokio::test]
     async fn returns_results() {
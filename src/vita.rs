@@ -1,12 +1,15 @@
+use crate::sources::custom::{JsonSource, JsonSourceDefinition};
+use crate::sources::scrape::{ScrapeDefinition, Scraper};
 use crate::sources::{
-    alienvault::AlienVault, anubisdb::AnubisDB, binaryedge::BinaryEdge, c99::C99,
-    certspotter::CertSpotter, chaos::Chaos, crtsh::Crtsh, facebook::Facebook,
-    hackertarget::HackerTarget, intelx::Intelx, passivetotal::PassiveTotal,
-    securitytrails::SecurityTrails, sonarsearch::SonarSearch, spyse::Spyse, sublister::Sublister,
+    alienvault::AlienVault, anubisdb::AnubisDB, binaryedge::BinaryEdge, c99::C99, censys::Censys,
+    certspotter::CertSpotter, chaos::Chaos, commoncrawl::CommonCrawl, crtsh::Crtsh, ctlogs::CtLogs,
+    dnsdumpster::DnsDumpster, facebook::Facebook, github::GitHub, hackertarget::HackerTarget,
+    intelx::Intelx, local::Local, passivetotal::PassiveTotal, securitytrails::SecurityTrails,
+    shodan::Shodan, sonarsearch::SonarSearch, spyse::Spyse, sublister::Sublister,
     threatcrowd::ThreatCrowd, threatminer::ThreatMiner, urlscan::UrlScan, virustotal::VirusTotal,
     wayback::Wayback,
 };
-use crate::{client, error::Result, DataSource};
+use crate::{client, error::Result, DataSource, Report, Subdomain};
 
 use futures::stream::{FuturesUnordered, StreamExt};
 use futures_core::stream::Stream;
@@ -27,14 +30,24 @@ enum Source {
     AnubisDB,
     BinaryEdge,
     C99,
+    Censys,
     CertSpotter,
     Chaos,
+    CommonCrawl,
     Crtsh,
+    CtLogs,
+    DnsDumpster,
+    DnsRepo,
     Facebook,
+    GitHub,
     HackerTarget,
     Intelx,
+    Local,
     PassiveTotal,
+    RapidDns,
     SecurityTrails,
+    Shodan,
+    SiteDossier,
     SonarSearch,
     Spyse,
     Sublister,
@@ -43,6 +56,19 @@ enum Source {
     UrlScan,
     VirusTotal,
     Wayback,
+    // A user defined json source, keyed by the name it was declared with
+    #[strum(disabled)]
+    Custom(String),
+}
+
+impl Source {
+    // The name the source is reported under.
+    fn name(&self) -> String {
+        match self {
+            Source::Custom(name) => name.clone(),
+            source => format!("{:?}", source),
+        }
+    }
 }
 
 // Configuration options for the `Runner`
@@ -66,6 +92,7 @@ pub struct Runner {
     client: Client,
     sources: HashMap<Source, Arc<dyn DataSource>>,
     config: Config,
+    report: Report,
 }
 
 impl Default for Runner {
@@ -75,6 +102,7 @@ impl Default for Runner {
             client: client!(config.timeout, config.timeout),
             sources: HashMap::new(),
             config,
+            report: Report::default(),
         }
     }
 }
@@ -96,10 +124,10 @@ impl Runner {
     pub fn exclude(mut self, excluded: &[&str]) -> Self {
         if !excluded.is_empty() {
             excluded.iter().for_each(|s| {
-                if let Ok(source) = Source::from_str(s) {
-                    info!("excluding {:?}", source);
-                    self.sources.remove(&source);
-                };
+                let source = Source::from_str(s).unwrap_or_else(|_| Source::Custom(s.to_string()));
+                if self.sources.remove(&source).is_some() {
+                    info!("excluding {}", source.name());
+                }
             });
         }
 
@@ -147,11 +175,57 @@ impl Runner {
                 Arc::new(SonarSearch::new(self.client.clone())),
             ),
             (Source::Wayback, Arc::new(Wayback::new(self.client.clone()))),
+            (
+                Source::CommonCrawl,
+                Arc::new(CommonCrawl::new(self.client.clone())),
+            ),
             (Source::UrlScan, Arc::new(UrlScan::new(self.client.clone()))),
             (Source::Crtsh, Arc::new(Crtsh::new(self.client.clone()))),
+            (
+                Source::DnsDumpster,
+                Arc::new(DnsDumpster::new(self.client.clone())),
+            ),
+            (
+                Source::RapidDns,
+                Arc::new(Scraper::new(
+                    self.client.clone(),
+                    ScrapeDefinition::rapiddns(),
+                )),
+            ),
+            (
+                Source::SiteDossier,
+                Arc::new(Scraper::new(
+                    self.client.clone(),
+                    ScrapeDefinition::sitedossier(),
+                )),
+            ),
+            (
+                Source::DnsRepo,
+                Arc::new(Scraper::new(
+                    self.client.clone(),
+                    ScrapeDefinition::dnsrepo(),
+                )),
+            ),
         ];
 
         self.sources.extend(free.into_iter());
+        // Chaos doesn't need a key when it's reading from the public dataset
+        let chaos = Chaos::new(self.client.clone());
+        if chaos.is_archive() {
+            self.sources.insert(Source::Chaos, Arc::new(chaos));
+        }
+
+        // The ct logs are only read when some have been configured
+        let ct_logs = CtLogs::new(self.client.clone());
+        if ct_logs.has_logs() {
+            self.sources.insert(Source::CtLogs, Arc::new(ct_logs));
+        }
+
+        // Local data is only searched when some has been configured
+        let local = Local::new();
+        if local.has_data() {
+            self.sources.insert(Source::Local, Arc::new(local));
+        }
         self
     }
 
@@ -212,22 +286,107 @@ impl Runner {
             ),
             (Source::Spyse, Arc::new(Spyse::new(self.client.clone()))),
             (Source::C99, Arc::new(C99::new(self.client.clone()))),
+            (Source::Censys, Arc::new(Censys::new(self.client.clone()))),
+            (Source::Shodan, Arc::new(Shodan::new(self.client.clone()))),
+            (Source::GitHub, Arc::new(GitHub::new(self.client.clone()))),
             (Source::Intelx, Arc::new(Intelx::new(self.client.clone()))),
             (Source::Wayback, Arc::new(Wayback::new(self.client.clone()))),
+            (
+                Source::CommonCrawl,
+                Arc::new(CommonCrawl::new(self.client.clone())),
+            ),
             (Source::UrlScan, Arc::new(UrlScan::new(self.client.clone()))),
             (Source::Crtsh, Arc::new(Crtsh::new(self.client.clone()))),
+            (
+                Source::DnsDumpster,
+                Arc::new(DnsDumpster::new(self.client.clone())),
+            ),
+            (
+                Source::RapidDns,
+                Arc::new(Scraper::new(
+                    self.client.clone(),
+                    ScrapeDefinition::rapiddns(),
+                )),
+            ),
+            (
+                Source::SiteDossier,
+                Arc::new(Scraper::new(
+                    self.client.clone(),
+                    ScrapeDefinition::sitedossier(),
+                )),
+            ),
+            (
+                Source::DnsRepo,
+                Arc::new(Scraper::new(
+                    self.client.clone(),
+                    ScrapeDefinition::dnsrepo(),
+                )),
+            ),
             (Source::Chaos, Arc::new(Chaos::new(self.client.clone()))),
         ];
 
         self.sources.extend(all.into_iter());
+        let ct_logs = CtLogs::new(self.client.clone());
+        if ct_logs.has_logs() {
+            self.sources.insert(Source::CtLogs, Arc::new(ct_logs));
+        }
+
+        // Local data is only searched when some has been configured
+        let local = Local::new();
+        if local.has_data() {
+            self.sources.insert(Source::Local, Arc::new(local));
+        }
         self
     }
 
+    /// Sets the only source to be SonarSearch, looking up the name of each input domain under
+    /// other top level domains.
+    pub fn tlds(mut self) -> Self {
+        self.sources.clear();
+        self.sources.insert(
+            Source::SonarSearch,
+            Arc::new(SonarSearch::new(self.client.clone()).tlds()),
+        );
+        self
+    }
+
+    /// Sets the only source to be SonarSearch, looking up the hostnames for each input ip
+    /// address or cidr range.
+    pub fn reverse_dns(mut self) -> Self {
+        self.sources.clear();
+        self.sources.insert(
+            Source::SonarSearch,
+            Arc::new(SonarSearch::new(self.client.clone()).reverse_dns()),
+        );
+        self
+    }
+
+    /// Adds user defined json sources, usually read from a config file with
+    /// `sources::custom::load`, to the current sources.
+    pub fn json_sources(mut self, definitions: Vec<JsonSourceDefinition>) -> Self {
+        for definition in definitions {
+            self.sources.insert(
+                Source::Custom(definition.name.clone()),
+                Arc::new(JsonSource::new(self.client.clone(), definition)),
+            );
+        }
+
+        self
+    }
+
+    /// Returns a handle to the report for this run. It's complete once the stream returned by
+    /// `run` has ended.
+    pub fn report(&self) -> Report {
+        self.report.clone()
+    }
+
     /// Fetches data from the sources concurrently
-    pub async fn run(self, hosts: HashSet<String>) -> Result<impl Stream<Item = Vec<String>>> {
-        let (tx, rx) = mpsc::channel::<Vec<String>>(CHAN_SIZE);
+    pub async fn run(self, hosts: HashSet<String>) -> Result<impl Stream<Item = Vec<Subdomain>>> {
+        let (tx, rx) = mpsc::channel::<Vec<Subdomain>>(CHAN_SIZE);
         let sources = Arc::new(self.sources);
         let max_concurrent = self.config.concurrency;
+        let report = self.report;
+        sources.values().for_each(|source| source.prepare(&hosts));
 
         let tx2 = tx.clone();
         tokio::spawn(async move {
@@ -239,11 +398,19 @@ impl Runner {
                     futures.next().await;
                 }
 
-                for source in sources.values() {
+                for (name, source) in sources.iter() {
+                    let name = name.name();
                     let source = Arc::clone(source);
                     let host = Arc::clone(&host);
+                    let report = report.clone();
                     let tx = tx2.clone();
-                    futures.push(tokio::spawn(async move { source.run(host, tx).await }));
+                    futures.push(tokio::spawn(async move {
+                        // Hold on to a sender until the result is recorded so the stream can't
+                        // end before the report is complete.
+                        let res = source.run(host, tx.clone()).await;
+                        report.record(&name, &res, source.notes());
+                        drop(tx);
+                    }));
                 }
             }
 
@@ -0,0 +1,210 @@
+use crate::{CleanExt, PostProcessor, Subdomain};
+use futures::future;
+use futures::stream::StreamExt;
+use futures_core::stream::Stream;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tracing::{debug, info, warn};
+
+/// The public certstream feed
+pub const DEFAULT_CERTSTREAM_URL: &str = "wss://certstream.calidog.io/";
+
+const CHAN_SIZE: usize = 255;
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+// Whether the stream handed out by `run` has been dropped. The sender can't be asked directly, but
+// reserving a slot in the channel fails once the receiver is gone. The slot is given straight
+// back so the next check asks the channel again.
+async fn is_closed<T>(tx: &mut mpsc::Sender<T>) -> bool {
+    let closed = future::poll_fn(|cx| tx.poll_ready(cx)).await.is_err();
+    tx.disarm();
+    closed
+}
+
+#[derive(Deserialize, Debug)]
+struct LeafCert {
+    #[serde(default)]
+    all_domains: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct CertificateUpdate {
+    leaf_cert: LeafCert,
+}
+
+// The messages sent by certstream. The full feed sends `certificate_update`s while the
+// `/domains-only` feed just sends the names as `dns_entries`.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "message_type", content = "data", rename_all = "snake_case")]
+enum CertstreamMessage {
+    CertificateUpdate(CertificateUpdate),
+    DnsEntries(Vec<String>),
+    #[serde(other)]
+    Other,
+}
+
+impl CertstreamMessage {
+    fn names(self) -> Vec<String> {
+        let names = match self {
+            CertstreamMessage::CertificateUpdate(update) => update.leaf_cert.all_domains,
+            CertstreamMessage::DnsEntries(names) => names,
+            CertstreamMessage::Other => Vec::new(),
+        };
+
+        // Wildcards tell us about the name they cover
+        names
+            .into_iter()
+            .map(|n| n.trim_start_matches("*.").to_string())
+            .collect()
+    }
+}
+
+/// `Watcher` subscribes to a certstream compatible websocket feed and returns new subdomains of
+/// the roots as certificates for them are logged.
+pub struct Watcher {
+    url: String,
+    cleaner: PostProcessor,
+}
+
+impl Watcher {
+    /// Creates a `Watcher` which filters the names from the public certstream feed with
+    /// `cleaner`.
+    pub fn new(cleaner: PostProcessor) -> Self {
+        Self {
+            url: DEFAULT_CERTSTREAM_URL.to_string(),
+            cleaner,
+        }
+    }
+
+    /// Sets the websocket url of the certstream feed.
+    pub fn url(mut self, url: &str) -> Self {
+        self.url = url.to_string();
+        self
+    }
+
+    // Returns the relevant names in a message which we haven't seen before.
+    fn new_names(&self, text: &str, seen: &mut HashSet<String>) -> Vec<Subdomain> {
+        let message: CertstreamMessage = match serde_json::from_str(text) {
+            Ok(m) => m,
+            Err(e) => {
+                debug!("skipping malformed certstream message: {}", e);
+                return Vec::new();
+            }
+        };
+
+        message
+            .names()
+            .into_iter()
+            .clean(&self.cleaner)
+            .filter(|s| seen.insert(s.host.clone()))
+            .collect()
+    }
+
+    /// Watches the feed until the returned stream is dropped, reconnecting whenever the
+    /// connection drops. Each subdomain is only returned the first time it's seen.
+    pub fn run(self) -> impl Stream<Item = Subdomain> {
+        let (mut tx, rx) = mpsc::channel::<Subdomain>(CHAN_SIZE);
+
+        tokio::spawn(async move {
+            let mut seen = HashSet::new();
+            let mut backoff = INITIAL_BACKOFF;
+
+            loop {
+                if is_closed(&mut tx).await {
+                    debug!("stopped watching certstream, the stream was dropped");
+                    return;
+                }
+
+                match connect_async(self.url.as_str()).await {
+                    Ok((mut ws, _)) => {
+                        info!("watching certstream at {}", &self.url);
+                        backoff = INITIAL_BACKOFF;
+
+                        while let Some(msg) = ws.next().await {
+                            let text = match msg {
+                                Ok(Message::Text(text)) => text,
+                                Ok(Message::Close(_)) => break,
+                                Ok(_) => continue,
+                                Err(e) => {
+                                    warn!("certstream connection failed: {}", e);
+                                    break;
+                                }
+                            };
+
+                            for subdomain in self.new_names(&text, &mut seen) {
+                                // Nobody is listening anymore
+                                if tx.send(subdomain).await.is_err() {
+                                    return;
+                                }
+                            }
+                        }
+                    }
+                    Err(e) => warn!("couldn't connect to certstream at {}: {}", &self.url, e),
+                }
+
+                if is_closed(&mut tx).await {
+                    debug!("stopped watching certstream, the stream was dropped");
+                    return;
+                }
+
+                info!("reconnecting to certstream in {:?}", backoff);
+                tokio::time::delay_for(backoff).await;
+                backoff = std::cmp::min(backoff * 2, MAX_BACKOFF);
+            }
+        });
+
+        rx
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn watcher() -> Watcher {
+        let mut cleaner = PostProcessor::default();
+        cleaner.any_root(vec!["hackerone.com".to_string()]);
+        Watcher::new(cleaner)
+    }
+
+    #[test]
+    fn filters_certificate_updates() {
+        let text = r#"{"message_type": "certificate_update", "data": {"update_type": "X509LogEntry",
+            "leaf_cert": {"all_domains": ["*.api.hackerone.com", "api.hackerone.com", "bugcrowd.com"]},
+            "cert_index": 1, "seen": 1604188800.0}}"#;
+        let mut seen = HashSet::new();
+        let hosts: Vec<String> = watcher()
+            .new_names(text, &mut seen)
+            .into_iter()
+            .map(|s| s.host)
+            .collect();
+        assert_eq!(hosts, vec!["api.hackerone.com"]);
+    }
+
+    #[test]
+    fn skips_seen_names() {
+        let text = r#"{"message_type": "dns_entries", "data": ["docs.hackerone.com"]}"#;
+        let watcher = watcher();
+        let mut seen = HashSet::new();
+        assert_eq!(watcher.new_names(text, &mut seen).len(), 1);
+        assert!(watcher.new_names(text, &mut seen).is_empty());
+    }
+
+    #[test]
+    fn ignores_heartbeats() {
+        let text = r#"{"message_type": "heartbeat", "timestamp": 1604188800.0}"#;
+        assert!(watcher().new_names(text, &mut HashSet::new()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn detects_dropped_stream() {
+        let (mut tx, rx) = mpsc::channel::<Subdomain>(1);
+        assert!(!is_closed(&mut tx).await);
+        drop(rx);
+        assert!(is_closed(&mut tx).await);
+    }
+}